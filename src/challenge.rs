@@ -0,0 +1,28 @@
+use crate::ids::{SessionToken};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Sha256};
+
+pub const NONCE_SIZE: usize = 32;
+
+/// Random value sent by the server to reclaim a session.
+/// The client proves that it knows the session token without sending it again.
+pub type Nonce = [u8; NONCE_SIZE];
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn create_mac(token: SessionToken, nonce: &Nonce) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&(token as u64).to_le_bytes()).unwrap();
+    mac.update(nonce);
+    mac
+}
+
+/// Answer to the challenge: `HMAC(token, nonce)`.
+pub fn prove(token: SessionToken, nonce: &Nonce) -> Vec<u8> {
+    create_mac(token, nonce).finalize().into_bytes().to_vec()
+}
+
+/// Checks that the proof was created with the token, in constant time.
+pub fn verify(token: SessionToken, nonce: &Nonce, proof: &[u8]) -> bool {
+    create_mac(token, nonce).verify(proof).is_ok()
+}
@@ -38,16 +38,33 @@ pub fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                 .default_value(&DEFAULT_LOG_FILE)
                 .help("Set the log file"),
         )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .short("n")
+                .value_name("NAME")
+                .validator(|name| match super::util::is_valid_player_name(&name) {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "The name must have between 1 and {} letters, numbers, '-' or '_'",
+                        super::util::MAX_PLAYER_NAME_LEN
+                    )),
+                })
+                .help("Set the player's name. Must be unique in the server"),
+        )
         .arg(
             Arg::with_name("character")
                 .long("character")
                 .short("c")
-                .value_name("CAPITAL_LETTER")
+                .value_name("SYMBOL")
                 .validator(|name| match super::util::is_valid_character_name(&name) {
                     true => Ok(()),
-                    false => Err("The character must be an unique capital letter".into()),
+                    false => Err("The character must be an unique letter or number".into()),
                 })
-                .help("Set the player's character. Must be unique in the server"),
+                .help(
+                    "Set the preferred player's character symbol. \
+                If it is already chosen, the server will assign other.",
+                ),
         )
         .arg(
             Arg::with_name("host")
@@ -60,16 +77,31 @@ pub fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                 })
                 .help("Set the server address (ip and port). Format example: 192.168.0.56:3549"),
         )
+        .arg(
+            Arg::with_name("room")
+                .long("room")
+                .short("r")
+                .value_name("NAME")
+                .help("Set the server room to join. By default, the main room of the server"),
+        )
+        .arg(
+            Arg::with_name("minimap")
+                .long("minimap")
+                .help("Show a minimap in the arena when the map does not fit in the terminal"),
+        )
 }
 
 pub fn run(matches: &ArgMatches) {
     let level = matches.value_of("log").unwrap().parse().unwrap();
     let file_name = matches.value_of("log-file").unwrap();
-    logger::init(level, logger::Output::File(file_name));
+    logger::init(level, logger::Output::File(file_name), logger::Format::Text, None);
 
     let config = Config {
+        player_name: matches.value_of("name").map(|name| name.into()),
         character: matches.value_of("character").map(|name| name.chars().next().unwrap()),
         server_addr: matches.value_of("host").map(|addr| addr.parse().unwrap()),
+        room: matches.value_of("room").map(|room| room.into()),
+        minimap: matches.is_present("minimap"),
     };
 
     Application::new(config).run();
@@ -2,5 +2,8 @@ use std::net::{SocketAddr};
 
 pub struct Config {
     pub server_addr: Option<SocketAddr>,
+    pub player_name: Option<String>,
     pub character: Option<char>,
+    pub room: Option<String>,
+    pub minimap: bool,
 }
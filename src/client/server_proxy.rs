@@ -1,11 +1,14 @@
 use crate::message::{
     LoginStatus, ServerInfo, ClientMessage, ServerMessage, LoggedKind, GameInfo, ArenaInfo, Frame,
-    GameEvent,
+    GameEvent, RoomStatus, LoggedPlayer, TerrainChange, PlayerStats, RatingEntry,
 };
 use crate::encoding::{self, Encoder};
 use crate::version::{self, Compatibility};
 use crate::direction::{Direction};
-use crate::ids::{SkillId};
+use crate::ids::{SkillId, SessionToken, UdpSessionId};
+use crate::datagram::{Datagram, SessionKey, Sender, ReplayWindow};
+use crate::challenge::{self, Nonce};
+use crate::util::{self};
 
 use message_io::node::{self, NodeHandler, NodeTask, NodeEvent};
 use message_io::network::{Endpoint, Transport, NetEvent};
@@ -14,6 +17,8 @@ use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration};
 
 const UDP_HANDSHAKE_MAX_ATTEMPS: usize = 10;
+const RECONNECTION_MAX_ATTEMPTS: usize = 10;
+const RECONNECTION_PERIOD: Duration = Duration::from_secs(1);
 
 lazy_static! {
     static ref EVENT_SAMPLING_TIMEOUT: Duration = Duration::from_millis(50);
@@ -23,11 +28,18 @@ lazy_static! {
 #[derive(Debug)]
 pub enum ApiCall {
     Connect(SocketAddr),
+    Reconnect(SocketAddr), // Connect again after a lost connection, retrying if it fails
     Disconnect,
     CheckVersion(String),
+    JoinRoom(String),
     SubscribeInfo,
-    Login(char),
+    QueryLeaderboard,
+    Login(String, Option<char>),
+    ReclaimSession(String, SessionToken), //player name, token
     Logout,
+    ChooseTeam(Option<u8>),
+    Ready(bool),
+    Chat(String),
     MovePlayer(Direction),
     CastSkill(Direction, SkillId),
 }
@@ -37,16 +49,25 @@ pub enum ApiCall {
 pub enum ServerEvent {
     ConnectionResult(ConnectionStatus),
     CheckedVersion(String, Compatibility),
+    RoomStatus(String, RoomStatus),
     StaticServerInfo(ServerInfo),
-    DynamicServerInfo(Vec<char>),
+    DynamicServerInfo(Vec<LoggedPlayer>),
+    Leaderboard(Vec<RatingEntry>),
+    Countdown(Option<Duration>),
     LoginStatus(LoginStatus),
+    Kicked(String),
+    Shutdown(String, Duration),
+    Chat(String, String),
+    Announcement(String),
     UdpReachable(bool),
     StartGame(GameInfo),
-    FinishGame,
+    PlayersJoined(GameInfo),
+    FinishGame(Vec<Vec<PlayerStats>>),
     GameEvent(GameEvent),
     GameStep(Frame),
     WaitArena(Duration),
     StartArena(ArenaInfo),
+    TerrainChanged(Vec<TerrainChange>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -67,6 +88,7 @@ impl ConnectionStatus {
 enum ProxyEvent {
     Api(ApiCall),
     HelloUdp(usize),
+    Reconnect(SocketAddr, usize),
 }
 
 pub struct ServerProxy {
@@ -80,7 +102,7 @@ impl ServerProxy {
 
         let mut connection = ServerConnection::new(node.clone());
         let node_task = listener.for_each_async(move |event| {
-            connection.process_event(event, |server_event| event_callback(server_event));
+            connection.process_event(event, &event_callback);
         });
 
         ServerProxy { node, node_task }
@@ -108,13 +130,23 @@ impl ServerApi {
     }
 }
 
+/// Identity given by the server to authenticate the datagrams of the session.
+struct UdpSession {
+    id: UdpSessionId,
+    key: SessionKey,
+    sequence: u64, // Last sequence number sent
+    window: ReplayWindow,
+}
+
 struct ConnectionInfo {
     ip: Option<IpAddr>,
     udp_port: Option<u16>,
     tcp: Option<Endpoint>,
     udp: Option<Endpoint>,
     has_udp_hasdshake: bool,
-    session_token: Option<usize>,
+    udp_session: Option<UdpSession>,
+    reconnecting: bool,
+    reclaim_token: Option<SessionToken>, // Until the reconnect challenge arrives
 }
 
 struct ServerConnection {
@@ -134,7 +166,9 @@ impl ServerConnection {
                 tcp: None,
                 udp: None,
                 has_udp_hasdshake: false,
-                session_token: None,
+                udp_session: None,
+                reconnecting: false,
+                reclaim_token: None,
             },
         }
     }
@@ -143,6 +177,36 @@ impl ServerConnection {
         self.node.network().send(endpoint, self.encoder.encode(message));
     }
 
+    /// Sends the message inside a datagram authenticated with the udp session key.
+    fn send_by_udp(&mut self, message: ClientMessage) {
+        match (self.connection.udp, self.connection.udp_session.as_mut()) {
+            (Some(udp), Some(session)) => {
+                session.sequence += 1;
+                let payload = self.encoder.encode(message);
+                let sequence = session.sequence;
+                let data =
+                    Datagram::seal(&session.key, Sender::Client, session.id, sequence, payload);
+                self.node.network().send(udp, &data);
+            }
+            _ => log::warn!("Attempt to send {:?} without udp session", message),
+        }
+    }
+
+    /// Sends the message by udp if the server trusts in it, otherwise by tcp.
+    fn send_by_faster(&mut self, message: ClientMessage) {
+        match self.connection.has_udp_hasdshake {
+            true => self.send_by_udp(message),
+            false => self.send_by_tcp(message),
+        }
+    }
+
+    fn send_by_tcp(&mut self, message: ClientMessage) {
+        match self.connection.tcp {
+            Some(tcp) => self.send_to_server(tcp, message),
+            None => log::warn!("Attempt to send {:?} without connection", message),
+        }
+    }
+
     fn connect(&mut self, addr: SocketAddr) -> ConnectionStatus {
         self.disconnect(); // Ensure there is no connection, reset if there is.
         match self.node.network().connect(Transport::FramedTcp, addr) {
@@ -161,7 +225,7 @@ impl ServerConnection {
 
     fn disconnect(&mut self) -> ConnectionStatus {
         self.connection.has_udp_hasdshake = false;
-        self.connection.session_token = None;
+        self.connection.udp_session = None;
         self.connection.udp_port = None;
         self.connection.udp = None;
         self.connection.ip = None;
@@ -174,10 +238,9 @@ impl ServerConnection {
 
     pub fn logout(&mut self) {
         self.connection.has_udp_hasdshake = false;
-        self.connection.session_token = None;
+        self.connection.udp_session = None;
         self.connection.udp = None;
-        let tcp = *self.connection.tcp.as_ref().unwrap();
-        self.send_to_server(tcp, ClientMessage::Logout);
+        self.send_by_tcp(ClientMessage::Logout);
     }
 
     pub fn process_event(&mut self, event: NodeEvent<ProxyEvent>, callback: impl Fn(ServerEvent)) {
@@ -186,36 +249,62 @@ impl ServerConnection {
             NodeEvent::Signal(signal) => match signal {
                 ProxyEvent::Api(api_call) => match api_call {
                     ApiCall::Connect(addr) => {
+                        self.connection.reconnecting = false;
                         let result = self.connect(addr);
                         callback(ServerEvent::ConnectionResult(result));
                     }
+                    ApiCall::Reconnect(addr) => {
+                        self.connection.reconnecting = true;
+                        self.process_reconnect(addr, 0, callback);
+                    }
                     ApiCall::Disconnect => {
+                        self.connection.reconnecting = false;
                         let result = self.disconnect();
                         callback(ServerEvent::ConnectionResult(result));
                     }
                     ApiCall::CheckVersion(version) => {
-                        let tcp = *self.connection.tcp.as_ref().unwrap();
-                        self.send_to_server(tcp, ClientMessage::Version(version));
+                        self.send_by_tcp(ClientMessage::Version(version));
+                    }
+                    ApiCall::JoinRoom(room) => {
+                        self.send_by_tcp(ClientMessage::JoinRoom(room));
                     }
                     ApiCall::SubscribeInfo => {
-                        let tcp = *self.connection.tcp.as_ref().unwrap();
-                        self.send_to_server(tcp, ClientMessage::SubscribeServerInfo);
+                        self.send_by_tcp(ClientMessage::SubscribeServerInfo);
+                    }
+                    ApiCall::QueryLeaderboard => {
+                        self.send_by_tcp(ClientMessage::QueryLeaderboard);
+                    }
+                    ApiCall::Login(name, character) => {
+                        self.send_by_tcp(ClientMessage::Login(name, character));
                     }
-                    ApiCall::Login(character) => {
-                        let tcp = *self.connection.tcp.as_ref().unwrap();
-                        self.send_to_server(tcp, ClientMessage::Login(character));
+                    ApiCall::ReclaimSession(name, token) => {
+                        // The token is only used to answer the challenge of the server
+                        self.connection.reclaim_token = Some(token);
+                        self.send_by_tcp(ClientMessage::Reconnect(name));
                     }
                     ApiCall::Logout => self.logout(),
+                    ApiCall::ChooseTeam(team) => {
+                        self.send_by_tcp(ClientMessage::ChooseTeam(team));
+                    }
+                    ApiCall::Ready(ready) => {
+                        self.send_by_tcp(ClientMessage::Ready(ready));
+                    }
+                    ApiCall::Chat(text) => {
+                        self.send_by_tcp(ClientMessage::Chat(text));
+                    }
                     ApiCall::MovePlayer(direction) => {
-                        let tcp = *self.connection.tcp.as_ref().unwrap();
-                        self.send_to_server(tcp, ClientMessage::MovePlayer(direction));
+                        self.send_by_faster(ClientMessage::MovePlayer(direction));
                     }
                     ApiCall::CastSkill(direction, id) => {
-                        let tcp = *self.connection.tcp.as_ref().unwrap();
-                        self.send_to_server(tcp, ClientMessage::CastSkill(direction, id));
+                        self.send_by_faster(ClientMessage::CastSkill(direction, id));
                     }
                 },
                 ProxyEvent::HelloUdp(attempt) => self.process_hello_udp(attempt, callback),
+                ProxyEvent::Reconnect(addr, attempt) => {
+                    if self.connection.reconnecting {
+                        self.process_reconnect(addr, attempt, callback);
+                    }
+                }
             },
             NodeEvent::Network(net_event) => match net_event {
                 NetEvent::Connected(_, _) => unreachable!(),
@@ -223,54 +312,124 @@ impl ServerConnection {
                     let result = ConnectionStatus::Lost;
                     callback(ServerEvent::ConnectionResult(result));
                 }
-                NetEvent::Message(endpoint, data) => match encoding::decode(data) {
-                    Some(message) => match message {
-                        ServerMessage::Version(server_version, compatibility) => {
-                            self.process_version(server_version, compatibility, callback);
-                        }
-                        ServerMessage::StaticServerInfo(info) => {
-                            self.process_static_server_info(info, callback);
-                        }
-                        ServerMessage::DynamicServerInfo(players) => {
-                            callback(ServerEvent::DynamicServerInfo(players));
-                        }
-                        ServerMessage::LoginStatus(character, status) => {
-                            self.process_login_status(character, status, callback);
-                        }
-                        ServerMessage::UdpConnected => {
-                            self.process_udp_connected(callback);
-                        }
-                        ServerMessage::StartGame(game_info) => {
-                            callback(ServerEvent::StartGame(game_info));
-                        }
-                        ServerMessage::FinishGame => {
-                            self.process_finish_game(callback);
-                        }
-                        ServerMessage::WaitArena(duration) => {
-                            callback(ServerEvent::WaitArena(duration));
-                        }
-                        ServerMessage::StartArena(arena_info) => {
-                            callback(ServerEvent::StartArena(arena_info));
-                        }
-                        ServerMessage::GameEvent(game_event) => {
-                            callback(ServerEvent::GameEvent(game_event));
-                        }
-                        ServerMessage::GameStep(frame) => {
-                            callback(ServerEvent::GameStep(frame));
+                NetEvent::Message(endpoint, data) => {
+                    if Some(endpoint) == self.connection.udp {
+                        // Unauthenticated datagrams are ignored, the endpoint is kept.
+                        return self.process_datagram(data, callback)
+                    }
+
+                    match encoding::decode(data) {
+                        Some(message) => self.process_server_message(message, callback),
+                        None => {
+                            log::error!(
+                                "Server sends an unknown message. Connection rejected. \
+                                Ensure the version compatibility.",
+                            );
+                            self.node.network().remove(endpoint.resource_id());
                         }
-                    },
-                    None => {
-                        log::error!(
-                            "Server sends an unknown message. Connection rejected. \
-                            Ensure the version compatibility.",
-                        );
-                        self.node.network().remove(endpoint.resource_id());
                     }
-                },
+                }
             },
         }
     }
 
+    fn process_server_message(&mut self, message: ServerMessage, callback: impl Fn(ServerEvent)) {
+        match message {
+            ServerMessage::Version(server_version, compatibility) => {
+                self.process_version(server_version, compatibility, callback);
+            }
+            ServerMessage::RoomList(rooms) => {
+                let names = rooms.into_iter().map(|room| room.settings.name);
+                log::info!("Server rooms: {}", util::format::items_to_string(names));
+            }
+            ServerMessage::RoomStatus(room, status) => {
+                self.process_room_status(room, status, callback);
+            }
+            ServerMessage::StaticServerInfo(info) => {
+                self.process_static_server_info(info, callback);
+            }
+            ServerMessage::DynamicServerInfo(players) => {
+                callback(ServerEvent::DynamicServerInfo(players));
+            }
+            ServerMessage::Leaderboard(leaderboard) => {
+                callback(ServerEvent::Leaderboard(leaderboard));
+            }
+            ServerMessage::Countdown(countdown) => {
+                callback(ServerEvent::Countdown(countdown));
+            }
+            ServerMessage::LoginStatus(name, status) => {
+                self.process_login_status(name, status, callback);
+            }
+            ServerMessage::Kicked(reason) => {
+                log::warn!("Kicked by the server: {}", reason);
+                callback(ServerEvent::Kicked(reason));
+            }
+            ServerMessage::Shutdown(reason, grace) => {
+                log::info!("The server is shutting down: {}", reason);
+                callback(ServerEvent::Shutdown(reason, grace));
+            }
+            ServerMessage::Chat(player_name, text) => {
+                callback(ServerEvent::Chat(player_name, text));
+            }
+            ServerMessage::Announcement(text) => {
+                log::info!("Server announcement: {}", text);
+                callback(ServerEvent::Announcement(text));
+            }
+            ServerMessage::ReconnectChallenge(nonce) => {
+                self.process_reconnect_challenge(nonce);
+            }
+            ServerMessage::UdpKey(id, key) => {
+                self.process_udp_key(id, key);
+            }
+            ServerMessage::UdpConnected => {
+                self.process_udp_connected(callback);
+            }
+            ServerMessage::StartGame(game_info) => {
+                callback(ServerEvent::StartGame(game_info));
+            }
+            ServerMessage::PlayersJoined(game_info) => {
+                callback(ServerEvent::PlayersJoined(game_info));
+            }
+            ServerMessage::FinishGame(stats) => {
+                // The udp session is kept for the next game
+                callback(ServerEvent::FinishGame(stats));
+            }
+            ServerMessage::WaitArena(duration) => {
+                callback(ServerEvent::WaitArena(duration));
+            }
+            ServerMessage::StartArena(arena_info) => {
+                callback(ServerEvent::StartArena(arena_info));
+            }
+            ServerMessage::TerrainChanged(changes) => {
+                callback(ServerEvent::TerrainChanged(changes));
+            }
+            ServerMessage::GameEvent(game_event) => {
+                callback(ServerEvent::GameEvent(game_event));
+            }
+            ServerMessage::GameStep(frame) => {
+                callback(ServerEvent::GameStep(frame));
+            }
+        }
+    }
+
+    fn process_datagram(&mut self, data: &[u8], callback: impl Fn(ServerEvent)) {
+        let session = match self.connection.udp_session.as_mut() {
+            Some(session) => session,
+            None => return log::warn!("Datagram received without udp session"),
+        };
+
+        let message = Datagram::decode(data)
+            .filter(|datagram| datagram.session_id == session.id)
+            .filter(|datagram| datagram.verify(&session.key, Sender::Server))
+            .filter(|datagram| session.window.accept(datagram.sequence))
+            .and_then(|datagram| encoding::decode(&datagram.payload));
+
+        match message {
+            Some(message) => self.process_server_message(message, callback),
+            None => log::warn!("Server datagram not authenticated or repeated. Ignored"),
+        }
+    }
+
     fn process_version(
         &mut self,
         server_version: String,
@@ -302,88 +461,133 @@ impl ServerConnection {
         callback(ServerEvent::CheckedVersion(server_version, compatibility));
     }
 
+    fn process_room_status(
+        &mut self,
+        room: String,
+        status: RoomStatus,
+        callback: impl Fn(ServerEvent),
+    ) {
+        match status {
+            RoomStatus::Created => log::info!("Room '{}' created", room),
+            RoomStatus::Joined => log::info!("Joined to room '{}'", room),
+            _ => log::warn!("Unable to join to room '{}': {:?}", room, status),
+        }
+        callback(ServerEvent::RoomStatus(room, status));
+    }
+
     fn process_static_server_info(&mut self, info: ServerInfo, callback: impl Fn(ServerEvent)) {
         self.connection.udp_port = Some(info.udp_port);
         callback(ServerEvent::StaticServerInfo(info));
     }
 
+    fn process_reconnect_challenge(&mut self, nonce: Nonce) {
+        match self.connection.reclaim_token.take() {
+            Some(token) => {
+                self.send_by_tcp(ClientMessage::ReconnectProof(challenge::prove(token, &nonce)))
+            }
+            None => log::warn!("Reconnect challenge received without a session to reclaim"),
+        }
+    }
+
     fn process_login_status(
         &mut self,
-        character: char,
+        name: String,
         status: LoginStatus,
         callback: impl Fn(ServerEvent),
     ) {
         match status {
-            LoginStatus::Logged(token, kind) => {
+            LoginStatus::Logged(_, character, kind) => {
                 let kind_str = match kind {
                     LoggedKind::FirstTime => "Logged",
                     LoggedKind::Reconnection => "Reconnected",
                 };
                 log::info!(
-                    "{} with name '{}' successful. Token Id: {}",
+                    "{} with name '{}' and character '{}' successful",
                     kind_str,
-                    character,
-                    token
+                    name,
+                    character
                 );
-
-                let udp_port = *self.connection.udp_port.as_ref().unwrap();
-                let ip = *self.connection.ip.as_ref().unwrap();
-                self.connection.session_token = Some(token);
-
-                let addr = SocketAddr::new(ip, udp_port);
-                let (endpoint, _) = self.node.network().connect(Transport::Udp, addr).unwrap();
-                self.connection.udp = Some(endpoint);
-                log::info!("Connection by udp on port {}", udp_port);
-                self.node.signals().send(ProxyEvent::HelloUdp(0));
             }
             LoginStatus::InvalidPlayerName => {
-                log::warn!("Invalid character name {}", character);
+                log::warn!("Invalid player name {}", name);
             }
             LoginStatus::AlreadyLogged => {
-                log::warn!("Character name '{}' already logged", character);
+                log::warn!("Player name '{}' already logged", name);
             }
             LoginStatus::PlayerLimit => {
                 log::error!("Server full");
             }
+            LoginStatus::InvalidToken => {
+                log::warn!("The session to reconnect no longer exists");
+            }
         }
         callback(ServerEvent::LoginStatus(status));
     }
 
+    fn process_reconnect(
+        &mut self,
+        addr: SocketAddr,
+        attempt: usize,
+        callback: impl Fn(ServerEvent),
+    ) {
+        match self.connect(addr) {
+            ConnectionStatus::Connected => {
+                self.connection.reconnecting = false;
+                callback(ServerEvent::ConnectionResult(ConnectionStatus::Connected));
+            }
+            _ if attempt + 1 < RECONNECTION_MAX_ATTEMPTS => {
+                log::info!("Reconnection attempt {} failed, retrying...", attempt + 1);
+                let reconnect = ProxyEvent::Reconnect(addr, attempt + 1);
+                self.node.signals().send_with_timer(reconnect, RECONNECTION_PERIOD);
+            }
+            status => {
+                log::error!("Unable to reconnect to server on {}", addr);
+                self.connection.reconnecting = false;
+                callback(ServerEvent::ConnectionResult(status));
+            }
+        }
+    }
+
+    fn process_udp_key(&mut self, id: UdpSessionId, key: SessionKey) {
+        let udp_port = *self.connection.udp_port.as_ref().unwrap();
+        let ip = *self.connection.ip.as_ref().unwrap();
+        self.connection.has_udp_hasdshake = false;
+        self.connection.udp_session =
+            Some(UdpSession { id, key, sequence: 0, window: ReplayWindow::default() });
+
+        let addr = SocketAddr::new(ip, udp_port);
+        let (endpoint, _) = self.node.network().connect(Transport::Udp, addr).unwrap();
+        self.connection.udp = Some(endpoint);
+        log::info!("Connection by udp on port {}", udp_port);
+        self.node.signals().send(ProxyEvent::HelloUdp(0));
+    }
+
     fn process_hello_udp(&mut self, attempt: usize, callback: impl Fn(ServerEvent)) {
         if !self.connection.has_udp_hasdshake {
-            match self.connection.session_token {
-                Some(token) => match self.connection.udp {
-                    Some(udp_endpoint) => {
-                        if attempt < UDP_HANDSHAKE_MAX_ATTEMPS {
-                            log::trace!("Udp handshake attempt: {}", attempt);
-                            self.send_to_server(udp_endpoint, ClientMessage::ConnectUdp(token));
-                            let next_time = (attempt * attempt) as u64 + 1;
-                            let next_message_timer = Duration::from_millis(next_time);
-                            let hello_udp = ProxyEvent::HelloUdp(attempt + 1);
-                            self.node.signals().send_with_timer(hello_udp, next_message_timer);
-                        }
-                        else {
-                            log::warn!("Unable to communicate by udp.");
-                            callback(ServerEvent::UdpReachable(false));
-                        }
+            match self.connection.udp_session {
+                Some(_) => {
+                    if attempt < UDP_HANDSHAKE_MAX_ATTEMPS {
+                        log::trace!("Udp handshake attempt: {}", attempt);
+                        self.send_by_udp(ClientMessage::ConnectUdp);
+                        let next_time = (attempt * attempt) as u64 + 1;
+                        let next_message_timer = Duration::from_millis(next_time);
+                        let hello_udp = ProxyEvent::HelloUdp(attempt + 1);
+                        self.node.signals().send_with_timer(hello_udp, next_message_timer);
                     }
-                    None => log::warn!("Attempt to send hello udp without known endpoint"),
-                },
-                None => log::warn!("Attempt to send hello udp without logged session"),
+                    else {
+                        log::warn!("Unable to communicate by udp.");
+                        callback(ServerEvent::UdpReachable(false));
+                    }
+                }
+                None => log::warn!("Attempt to send hello udp without udp session"),
             }
         }
     }
 
     fn process_udp_connected(&mut self, callback: impl Fn(ServerEvent)) {
-        let tcp = *self.connection.tcp.as_ref().unwrap();
-        self.send_to_server(tcp, ClientMessage::TrustUdp);
+        self.send_by_tcp(ClientMessage::TrustUdp);
         self.connection.has_udp_hasdshake = true;
         log::info!("Client udp successful reachable from server");
         callback(ServerEvent::UdpReachable(true));
     }
-
-    fn process_finish_game(&mut self, callback: impl Fn(ServerEvent)) {
-        self.connection.has_udp_hasdshake = false;
-        callback(ServerEvent::FinishGame);
-    }
 }
@@ -2,19 +2,24 @@ use super::server_proxy::{ConnectionStatus};
 use super::configuration::{Config};
 
 use crate::version::{Compatibility};
-use crate::message::{LoginStatus, EntityData, SpellData, Terrain};
+use crate::message::{
+    LoginStatus, PlayerData, EntityData, SpellData, ItemData, Terrain, RoomStatus, LoggedPlayer,
+    GameModeKind, KillCause, PlayerStats, RatingEntry,
+};
 use crate::character::{CharacterId, Character};
 use crate::direction::{Direction};
 use crate::vec2::{Vec2};
-use crate::ids::{EntityId, SpellId};
+use crate::ids::{EntityId, SpellId, ItemId, SessionToken};
 
 use std::net::{SocketAddr};
-use std::time::{Instant};
+use std::time::{Instant, Duration};
 use std::collections::{HashMap};
 
 pub struct User {
+    pub player_name: Option<String>,
     pub character_symbol: Option<char>,
     pub login_status: Option<LoginStatus>,
+    pub session_token: Option<SessionToken>, // To reclaim the session after a lost connection
 }
 
 impl User {
@@ -32,9 +37,22 @@ pub struct VersionInfo {
 }
 
 pub struct StaticGameInfo {
-    pub players_number: usize,
+    pub min_players: usize, // Ready players to start a game
+    pub max_players: usize,
     pub map_size: usize,
     pub winner_points: usize,
+    pub teams: usize, // 0 for free for all
+    pub friendly_fire: bool,
+    pub mode: GameModeKind,
+    pub game_duration: Duration,
+    pub sudden_death: Option<Duration>,
+    pub late_join: bool, // Ready players can join a running game
+}
+
+impl StaticGameInfo {
+    pub fn has_teams(&self) -> bool {
+        self.teams > 0
+    }
 }
 
 pub struct UserPlayer {
@@ -44,17 +62,40 @@ pub struct UserPlayer {
 
 pub struct Arena {
     pub user_player: UserPlayer,
+    pub players: Vec<PlayerData>, // Seen or not, by position of server.arena.players Vec.
     pub entities: HashMap<EntityId, EntityData>,
     pub spells: HashMap<SpellId, SpellData>,
+    pub items: HashMap<ItemId, ItemData>,
     pub size: usize,
     pub ground: Vec<Terrain>,
+    pub visible: Vec<bool>,  // Ground currently seen by the user
+    pub explored: Vec<bool>, // Ground seen by the user at any moment of the arena
+    pub sudden_death_timestamp: Option<Instant>,
+    pub finish_timestamp: Option<Instant>, // Only with arena time limit
 }
 
 impl Arena {
-    pub fn terrain(&self, position: Vec2) -> Terrain {
+    fn index(&self, position: Vec2) -> usize {
         assert!(position.x >= 0 && position.x < self.size as i32);
         assert!(position.y >= 0 && position.y < self.size as i32);
-        self.ground[position.y as usize * self.size + position.x as usize]
+        position.y as usize * self.size + position.x as usize
+    }
+
+    pub fn terrain(&self, position: Vec2) -> Terrain {
+        self.ground[self.index(position)]
+    }
+
+    pub fn set_terrain(&mut self, position: Vec2, terrain: Terrain) {
+        let index = self.index(position);
+        self.ground[index] = terrain;
+    }
+
+    pub fn is_visible(&self, position: Vec2) -> bool {
+        self.visible[self.index(position)]
+    }
+
+    pub fn is_explored(&self, position: Vec2) -> bool {
+        self.explored[self.index(position)]
     }
 }
 
@@ -67,18 +108,32 @@ pub enum GameStatus {
 
 pub struct Player {
     pub id: usize, // The position of server.arena.players Vec.
+    pub name: String,
     pub character_id: CharacterId,
     pub entity_id: EntityId,
+    pub team: Option<usize>,
     pub points: usize,
+    pub kills: usize,
+    pub deaths: usize,
+}
+
+pub struct Kill {
+    pub victim: usize, // Player id
+    pub cause: KillCause,
+    pub time: Instant,
 }
 
 pub struct Game {
     pub status: GameStatus,
     pub next_arena_timestamp: Option<Instant>,
+    pub finish_timestamp: Option<Instant>, // Only for timed games
     pub arena_number: usize,
     pub arena: Option<Arena>,
     pub characters: HashMap<CharacterId, Character>,
     pub players: Vec<Player>,
+    pub team_points: Vec<usize>, // Empty for free for all
+    pub kill_feed: Vec<Kill>,
+    pub match_stats: Vec<Vec<PlayerStats>>, // By arena and player, once the game finishes
 }
 
 impl Game {
@@ -91,21 +146,35 @@ impl Game {
     }
 }
 
+pub struct ChatMessage {
+    pub player_name: String,
+    pub text: String,
+    pub announcement: bool, // Written by the server administrator
+}
+
 pub struct Server {
     pub addr: Option<SocketAddr>,
     pub connection_status: ConnectionStatus,
+    pub reconnecting: bool,
+    pub kick_reason: Option<String>, // Until a new connection
+    pub shutdown_reason: Option<String>, // Until a new connection
+    pub countdown_timestamp: Option<Instant>, // When the next game starts
+    pub room: Option<String>,
+    pub room_status: Option<RoomStatus>,
     pub udp_port: Option<u16>,
     pub udp_confirmed: Option<bool>,
     pub version_info: Option<VersionInfo>,
     pub game_info: Option<StaticGameInfo>,
-    pub logged_players: Vec<char>,
+    pub logged_players: Vec<LoggedPlayer>,
+    pub leaderboard: Option<Vec<RatingEntry>>, // None until the server sends it
+    pub chat_messages: Vec<ChatMessage>,
     pub game: Game,
 }
 
 impl Server {
     pub fn is_full(&self) -> bool {
-        if let Some(StaticGameInfo { players_number, .. }) = self.game_info {
-            if players_number == self.logged_players.len() {
+        if let Some(StaticGameInfo { max_players, .. }) = self.game_info {
+            if max_players == self.logged_players.len() {
                 return true
             }
         }
@@ -136,24 +205,49 @@ pub struct State {
 impl State {
     pub fn new(config: &Config) -> State {
         State {
-            user: User { character_symbol: config.character, login_status: None },
+            user: User {
+                player_name: config.player_name.clone(),
+                character_symbol: config.character,
+                login_status: None,
+                session_token: None,
+            },
             server: Server {
                 addr: config.server_addr,
                 connection_status: ConnectionStatus::NotConnected,
+                reconnecting: false,
+                kick_reason: None,
+                shutdown_reason: None,
+                countdown_timestamp: None,
+                room: config.room.clone(),
+                room_status: None,
                 udp_port: None,
                 udp_confirmed: None,
                 version_info: None,
                 game_info: None,
                 logged_players: Vec::new(),
+                leaderboard: None,
+                chat_messages: Vec::new(),
                 game: Game {
                     status: GameStatus::NotStarted,
                     arena_number: 0,
                     next_arena_timestamp: None,
+                    finish_timestamp: None,
                     arena: None,
                     characters: HashMap::new(),
                     players: Vec::new(),
+                    team_points: Vec::new(),
+                    kill_feed: Vec::new(),
+                    match_stats: Vec::new(),
                 },
             },
         }
     }
+
+    /// Ready for the next game in the lobby.
+    pub fn is_user_ready(&self) -> bool {
+        self.server
+            .logged_players
+            .iter()
+            .any(|player| player.ready && Some(&player.name) == self.user.player_name.as_ref())
+    }
 }
@@ -1,24 +1,36 @@
-use super::state::{State, StaticGameInfo, VersionInfo, GameStatus, Arena, Player, UserPlayer};
+use super::state::{
+    State, StaticGameInfo, VersionInfo, GameStatus, Arena, Player, UserPlayer, ChatMessage, Kill,
+};
 use super::server_proxy::{ServerApi, ApiCall, ConnectionStatus, ServerEvent};
 
-use crate::message::{GameEvent};
-use crate::character::{CharacterId};
+use crate::message::{GameEvent, RoomStatus, LoginStatus, KillCause};
 use crate::direction::{Direction};
 use crate::ids::{EntityId, SkillId};
 use crate::version::{self};
+use crate::sight::{self};
 
 use std::net::{SocketAddr};
 use std::time::{Instant};
 use std::collections::{HashMap};
 
+/// Number of chat messages kept in the state.
+const CHAT_HISTORY_SIZE: usize = 100;
+
+/// Number of kills kept in the state.
+const KILL_FEED_SIZE: usize = 20;
+
 /// Action API
 #[derive(Debug)]
 pub enum Action {
     StartApp,
     Connect(SocketAddr),
     Disconnect,
-    Login(char),
+    Login(String, Option<char>),
     Logout,
+    ChooseTeam(Option<u8>),
+    Ready(bool),
+    Chat(String),
+    QueryLeaderboard,
     CloseGame,
     CloseApp,
     MovePlayer(Direction),
@@ -64,20 +76,43 @@ impl Store {
                 self.server.call(ApiCall::Disconnect);
             }
 
-            Action::Login(character) => {
-                self.state.user.character_symbol = Some(character);
-                self.server.call(ApiCall::Login(character));
+            Action::Login(name, character) => {
+                self.state.user.player_name = Some(name.clone());
+                self.state.user.character_symbol = character;
+                self.server.call(ApiCall::Login(name, character));
             }
 
             Action::Logout => {
+                self.state.user.player_name = None;
                 self.state.user.character_symbol = None;
                 self.state.user.login_status = None;
+                self.state.user.session_token = None;
                 self.server.call(ApiCall::Logout);
             }
 
+            Action::ChooseTeam(team) => {
+                self.server.call(ApiCall::ChooseTeam(team));
+            }
+
+            Action::Ready(ready) => {
+                self.server.call(ApiCall::Ready(ready));
+            }
+
+            Action::Chat(text) => {
+                self.server.call(ApiCall::Chat(text));
+            }
+
+            Action::QueryLeaderboard => {
+                self.server.call(ApiCall::QueryLeaderboard);
+            }
+
             Action::CloseGame => {
                 self.state.server.game.status = GameStatus::NotStarted;
                 self.state.server.game.arena = None;
+                if self.state.server.leaderboard.is_some() {
+                    // The ratings have changed with the game
+                    self.server.call(ApiCall::QueryLeaderboard);
+                }
             }
 
             Action::CloseApp => {
@@ -97,16 +132,34 @@ impl Store {
             Action::ServerEvent(server_event) => match server_event {
                 ServerEvent::ConnectionResult(status) => {
                     self.state.server.connection_status = status;
+                    let can_reconnect = matches!(status, ConnectionStatus::Lost)
+                        && self.state.user.session_token.is_some()
+                        && !self.state.server.reconnecting;
+
                     if let ConnectionStatus::Connected = status {
+                        self.state.server.kick_reason = None;
+                        self.state.server.shutdown_reason = None;
                         self.server.call(ApiCall::CheckVersion(version::current().into()));
                     }
+                    else if let (true, Some(addr)) = (can_reconnect, self.state.server.addr) {
+                        // The user and the game are kept until the session is reclaimed
+                        self.state.server.reconnecting = true;
+                        self.state.server.udp_confirmed = None;
+                        self.server.call(ApiCall::Reconnect(addr));
+                    }
                     else {
                         //No connected (no matter the reason)
                         self.state.server.game.status = GameStatus::Finished;
                         self.state.server.udp_confirmed = None;
+                        self.state.server.room_status = None;
+                        self.state.user.player_name = None;
                         self.state.user.character_symbol = None;
                         self.state.user.login_status = None;
+                        self.state.user.session_token = None;
+                        self.state.server.reconnecting = false;
                         self.state.server.logged_players = Vec::new();
+                        self.state.server.leaderboard = None;
+                        self.state.server.chat_messages = Vec::new();
                         self.state.server.game.arena = None;
                         self.state.server.game_info = None;
                     }
@@ -117,7 +170,10 @@ impl Store {
                     self.state.server.version_info = Some(version_info);
 
                     if compatibility.is_compatible() {
-                        self.server.call(ApiCall::SubscribeInfo);
+                        match &self.state.server.room {
+                            Some(room) => self.server.call(ApiCall::JoinRoom(room.clone())),
+                            None => self.server.call(ApiCall::SubscribeInfo),
+                        }
                     }
                     else {
                         // Protect the client against an unknown or not compatible server version
@@ -125,18 +181,42 @@ impl Store {
                     }
                 }
 
+                ServerEvent::RoomStatus(room, status) => {
+                    self.state.server.room_status = Some(status);
+                    if let RoomStatus::Joined | RoomStatus::Created = status {
+                        self.state.server.room = Some(room);
+                        self.server.call(ApiCall::SubscribeInfo);
+                    }
+                }
+
                 ServerEvent::StaticServerInfo(info) => {
                     let game_info = StaticGameInfo {
-                        players_number: info.players_number as usize,
+                        min_players: info.min_players as usize,
+                        max_players: info.max_players as usize,
                         map_size: info.map_size as usize,
                         winner_points: info.winner_points as usize,
+                        teams: info.teams as usize,
+                        friendly_fire: info.friendly_fire,
+                        mode: info.mode,
+                        game_duration: info.game_duration,
+                        sudden_death: info.sudden_death,
+                        late_join: info.late_join.is_some(),
                     };
+                    self.state.server.room = Some(info.room);
                     self.state.server.udp_port = Some(info.udp_port);
                     self.state.server.game_info = Some(game_info);
                     self.state.server.logged_players = info.logged_players;
+                    self.state.server.countdown_timestamp =
+                        info.countdown.map(|countdown| Instant::now() + countdown);
 
-                    if let Some(character) = self.state.user.character_symbol {
-                        self.server.call(ApiCall::Login(character));
+                    if let (Some(token), Some(name)) =
+                        (self.state.user.session_token, self.state.user.player_name.clone())
+                    {
+                        self.server.call(ApiCall::ReclaimSession(name, token));
+                    }
+                    else if let Some(name) = self.state.user.player_name.clone() {
+                        let character = self.state.user.character_symbol;
+                        self.server.call(ApiCall::Login(name, character));
                     }
                 }
 
@@ -144,8 +224,66 @@ impl Store {
                     self.state.server.logged_players = logged_players;
                 }
 
+                ServerEvent::Leaderboard(leaderboard) => {
+                    self.state.server.leaderboard = Some(leaderboard);
+                }
+
+                ServerEvent::Countdown(countdown) => {
+                    self.state.server.countdown_timestamp =
+                        countdown.map(|countdown| Instant::now() + countdown);
+                }
+
                 ServerEvent::LoginStatus(status) => {
+                    match status {
+                        LoginStatus::Logged(token, character, _) => {
+                            self.state.user.character_symbol = Some(character);
+                            if token.is_some() {
+                                self.state.user.session_token = token;
+                            }
+                        }
+                        LoginStatus::InvalidToken => {
+                            // The session no longer exists, login again as a new player
+                            self.state.user.session_token = None;
+                            self.state.server.game.status = GameStatus::NotStarted;
+                            self.state.server.game.arena = None;
+                            if let Some(name) = self.state.user.player_name.clone() {
+                                let character = self.state.user.character_symbol;
+                                self.server.call(ApiCall::Login(name, character));
+                            }
+                        }
+                        _ => (),
+                    }
                     self.state.user.login_status = Some(status);
+                    self.state.server.reconnecting = false;
+                }
+
+                ServerEvent::Kicked(reason) => {
+                    // The session can not be reclaimed
+                    self.state.user.session_token = None;
+                    self.state.server.kick_reason = Some(reason);
+                }
+
+                ServerEvent::Shutdown(reason, grace) => {
+                    // The server will not be there to reclaim the session
+                    self.state.user.session_token = None;
+                    let text = format!(
+                        "The server is shutting down in {} seconds: {}",
+                        grace.as_secs(),
+                        reason
+                    );
+                    let player_name = "Server".into();
+                    self.add_chat_message(ChatMessage { player_name, text, announcement: true });
+                    self.state.server.shutdown_reason = Some(reason);
+                }
+
+                ServerEvent::Chat(player_name, text) => {
+                    let message = ChatMessage { player_name, text, announcement: false };
+                    self.add_chat_message(message);
+                }
+
+                ServerEvent::Announcement(text) => {
+                    let player_name = "Server".into();
+                    self.add_chat_message(ChatMessage { player_name, text, announcement: true });
                 }
 
                 ServerEvent::UdpReachable(value) => {
@@ -153,6 +291,7 @@ impl Store {
                 }
 
                 ServerEvent::StartGame(game_info) => {
+                    self.state.server.countdown_timestamp = None;
                     self.state.server.game.status = GameStatus::Started;
                     self.state.server.game.characters = game_info
                         .characters
@@ -164,22 +303,82 @@ impl Store {
                         .players
                         .into_iter()
                         .enumerate()
-                        .map(|(index, (character_id, points))| Player {
+                        .map(|(index, player)| Player {
                             id: index,
-                            character_id,
+                            name: player.name,
+                            character_id: player.character_id,
                             entity_id: EntityId::NONE,
-                            points,
+                            team: player.team.map(|team| team as usize),
+                            points: player.points,
+                            kills: player.kills,
+                            deaths: player.deaths,
+                        })
+                        .collect();
+
+                    self.state.server.game.kill_feed = Vec::new();
+                    self.state.server.game.match_stats = Vec::new();
+
+                    self.state.server.game.team_points = game_info.team_points;
+                    self.state.server.game.finish_timestamp =
+                        game_info.remaining_time.map(|remaining| Instant::now() + remaining);
+                }
+
+                ServerEvent::PlayersJoined(game_info) => {
+                    let game = &mut self.state.server.game;
+                    game.characters = game_info
+                        .characters
+                        .into_iter()
+                        .map(|character| (character.id(), character))
+                        .collect();
+
+                    // The positions of the previous players in the list may change
+                    let previous_players = std::mem::take(&mut game.players);
+                    game.players = game_info
+                        .players
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, player)| Player {
+                            id: index,
+                            // The previous players keep their entity until the next arena
+                            entity_id: previous_players
+                                .iter()
+                                .find(|previous| previous.name == player.name)
+                                .map(|previous| previous.entity_id)
+                                .unwrap_or(EntityId::NONE),
+                            name: player.name,
+                            character_id: player.character_id,
+                            team: player.team.map(|team| team as usize),
+                            points: player.points,
+                            kills: player.kills,
+                            deaths: player.deaths,
                         })
                         .collect();
+
+                    let players = &game.players;
+                    let new_id = |id: usize| {
+                        let name = &previous_players[id].name;
+                        players.iter().position(|player| &player.name == name).unwrap()
+                    };
+                    for kill in &mut game.kill_feed {
+                        kill.victim = new_id(kill.victim);
+                        if let KillCause::Player(killer) = kill.cause {
+                            kill.cause = KillCause::Player(new_id(killer));
+                        }
+                    }
+                    if let Some(arena) = game.arena.as_mut() {
+                        arena.user_player.player_id = new_id(arena.user_player.player_id);
+                    }
                 }
 
-                ServerEvent::FinishGame => {
+                ServerEvent::FinishGame(stats) => {
                     self.state.server.game.status = GameStatus::Finished;
-                    self.state.server.udp_confirmed = None;
-                    self.state.user.character_symbol = None;
-                    self.state.user.login_status = None;
-                    self.state.server.game.arena_mut().entities = HashMap::new();
-                    self.state.server.game.arena_mut().spells = HashMap::new();
+                    self.state.server.game.match_stats = stats;
+                    // The user keeps logged in the lobby for the next game
+                    if let Some(arena) = self.state.server.game.arena.as_mut() {
+                        arena.entities = HashMap::new();
+                        arena.spells = HashMap::new();
+                        arena.items = HashMap::new();
+                    }
                 }
 
                 ServerEvent::WaitArena(duration) => {
@@ -195,8 +394,10 @@ impl Store {
                     }
 
                     self.state.server.game.arena = Some(Arena {
+                        players: Vec::new(),
                         entities: HashMap::new(),
                         spells: HashMap::new(),
+                        items: HashMap::new(),
                         user_player: UserPlayer {
                             player_id: self
                                 .state
@@ -205,29 +406,61 @@ impl Store {
                                 .players
                                 .iter()
                                 .enumerate()
-                                .find(|(_, player)| match player.character_id {
-                                    CharacterId::Player(symbol) => {
-                                        symbol == self.state.user.character_symbol.unwrap()
-                                    }
-                                    _ => false,
+                                .find(|(_, player)| {
+                                    Some(&player.name) == self.state.user.player_name.as_ref()
                                 })
                                 .map(|(index, _)| index)
                                 .unwrap(),
                             direction: Direction::Down,
                         },
                         size: self.state.server.game_info().map_size,
+                        visible: vec![false; arena_info.ground.len()],
+                        explored: vec![false; arena_info.ground.len()],
+                        sudden_death_timestamp: arena_info
+                            .sudden_death
+                            .map(|sudden_death| Instant::now() + sudden_death),
+                        finish_timestamp: arena_info
+                            .time_limit
+                            .map(|time_limit| Instant::now() + time_limit),
                         ground: arena_info.ground,
                     });
                 }
 
+                ServerEvent::TerrainChanged(changes) => {
+                    let arena = self.state.server.game.arena_mut();
+                    for change in changes {
+                        arena.set_terrain(change.position, change.terrain);
+                    }
+                }
+
                 ServerEvent::GameEvent(game_event) => {
-                    let GameEvent::PlayerPointsUpdated(player_points) = game_event;
-                    for (i, points) in player_points.into_iter().enumerate() {
-                        self.state.server.game.players[i].points = points;
+                    match game_event {
+                        GameEvent::PlayerPointsUpdated(player_points) => {
+                            for (i, points) in player_points.into_iter().enumerate() {
+                                self.state.server.game.players[i].points = points;
+                            }
+                        }
+                        GameEvent::TeamPointsUpdated(team_points) => {
+                            self.state.server.game.team_points = team_points;
+                        }
+                        GameEvent::PlayerKilled(victim, cause) => {
+                            let game = &mut self.state.server.game;
+                            game.players[victim].deaths += 1;
+                            if let KillCause::Player(killer) = cause {
+                                game.players[killer].kills += 1;
+                            }
+
+                            game.kill_feed.push(Kill { victim, cause, time: Instant::now() });
+                            if game.kill_feed.len() > KILL_FEED_SIZE {
+                                game.kill_feed.remove(0);
+                            }
+                        }
                     }
                 }
 
                 ServerEvent::GameStep(frame) => {
+                    self.state.server.game.arena_mut().players = frame.players;
+
                     self.state.server.game.arena_mut().entities = frame
                         .entities
                         .into_iter()
@@ -239,8 +472,53 @@ impl Store {
                         .into_iter()
                         .map(|spell| (spell.id, spell))
                         .collect::<HashMap<_, _>>();
+
+                    self.state.server.game.arena_mut().items = frame
+                        .items
+                        .into_iter()
+                        .map(|item| (item.id, item))
+                        .collect::<HashMap<_, _>>();
+
+                    self.update_sight();
                 }
             },
         }
     }
+
+    fn add_chat_message(&mut self, message: ChatMessage) {
+        let messages = &mut self.state.server.chat_messages;
+        messages.push(message);
+        if messages.len() > CHAT_HISTORY_SIZE {
+            messages.remove(0);
+        }
+    }
+
+    /// Computes the same sight as the server, from the user and its teammates.
+    /// Without anyone alive, the server sends the whole arena.
+    fn update_sight(&mut self) {
+        let game = &self.state.server.game;
+        let arena = game.arena();
+        let user = &game.players[arena.user_player.player_id];
+        let origins = game
+            .players
+            .iter()
+            .filter(|player| match user.team {
+                Some(team) => player.team == Some(team),
+                None => player.id == user.id,
+            })
+            .filter_map(|player| arena.entities.get(&player.entity_id))
+            .map(|entity| entity.position)
+            .collect::<Vec<_>>();
+
+        let visible = match origins.is_empty() {
+            true => vec![true; arena.ground.len()],
+            false => sight::visible_area(&arena.ground, arena.size, &origins),
+        };
+
+        let arena = self.state.server.game.arena_mut();
+        for (explored, &visible) in arena.explored.iter_mut().zip(visible.iter()) {
+            *explored |= visible;
+        }
+        arena.visible = visible;
+    }
 }
@@ -2,4 +2,6 @@ pub mod gui;
 pub mod menu;
 pub mod waiting_room;
 pub mod arena;
+pub mod chat;
+pub mod camera;
 pub mod util;
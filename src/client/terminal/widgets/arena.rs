@@ -1,17 +1,21 @@
 use super::util::{self};
+use super::chat::{Chat, ChatPanelWidget};
+use super::camera::{Camera};
 
 use crate::client::state::{State, GameStatus, Player};
 use crate::client::store::{Store, Action};
 use crate::client::terminal::input::{InputEvent};
+use crate::client::terminal::renderer::{Cursor};
 use crate::client::configuration::{Config};
 
 use crate::direction::{Direction};
 use crate::character::{CharacterId, Character};
-use crate::message::{EntityData};
+use crate::message::{PlayerData, EntityData, KillCause, PlayerStats};
 use crate::ids::{SkillId, EntityId};
+use crate::vec2::{Vec2};
 
 use tui::buffer::{Buffer};
-use tui::widgets::{Paragraph, Block, Borders, BorderType, Widget};
+use tui::widgets::{Paragraph, Block, Borders, BorderType, Clear, Widget, StatefulWidget};
 use tui::layout::{Layout, Constraint, Direction as Dir, Rect, Alignment, Margin};
 use tui::style::{Style, Modifier, Color};
 use tui::text::{Span, Spans};
@@ -24,19 +28,50 @@ use std::collections::{HashMap};
 pub struct Arena {
     previous_entities: HashMap<EntityId, EntityData>,
     damaged_entities: HashMap<EntityId, Instant>,
+    chat: Chat,
+    camera: Camera,
+    display_size: (u16, u16),
+    minimap: bool,
+    scoreboard_page: Option<usize>, // 0 for the whole game, then one page by arena
 }
 
 impl Arena {
-    pub fn new(_config: &Config) -> Arena {
-        Arena { previous_entities: HashMap::new(), damaged_entities: HashMap::new() }
+    pub fn new(config: &Config) -> Arena {
+        Arena {
+            previous_entities: HashMap::new(),
+            damaged_entities: HashMap::new(),
+            chat: Chat::new(),
+            camera: Camera::new(),
+            display_size: crossterm::terminal::size().unwrap_or((80, 24)),
+            minimap: config.minimap,
+            scoreboard_page: None,
+        }
     }
 
     pub fn process_event(&mut self, store: &mut Store, event: InputEvent) {
         match event {
+            InputEvent::KeyPressed(key_event) if self.chat.is_open() => {
+                self.chat.key_pressed(store, key_event);
+            }
             InputEvent::KeyPressed(key_event) => match key_event.code {
+                KeyCode::Tab if store.state().user.is_logged() => self.chat.open(),
                 KeyCode::Enter => {
                     if let GameStatus::Finished = store.state().server.game.status {
-                        store.dispatch(Action::CloseGame);
+                        match self.scoreboard_page {
+                            Some(_) => store.dispatch(Action::CloseGame),
+                            None => self.scoreboard_page = Some(0),
+                        }
+                    }
+                }
+                KeyCode::Left => {
+                    if let Some(page) = self.scoreboard_page {
+                        self.scoreboard_page = Some(page.saturating_sub(1));
+                    }
+                }
+                KeyCode::Right => {
+                    if let Some(page) = self.scoreboard_page {
+                        let arenas = store.state().server.game.match_stats.len();
+                        self.scoreboard_page = Some((page + 1).min(arenas));
                     }
                 }
                 KeyCode::Char(c) => {
@@ -53,11 +88,21 @@ impl Arena {
                 }
                 _ => (),
             },
-            InputEvent::ResizeDisplay(..) => {}
+            InputEvent::ResizeDisplay(width, height) => {
+                self.display_size = (width as u16, height as u16);
+            }
         }
     }
 
     pub fn update(&mut self, state: &State) {
+        if !state.user.is_logged() {
+            self.chat.close();
+        }
+
+        if !matches!(state.server.game.status, GameStatus::Finished) {
+            self.scoreboard_page = None;
+        }
+
         let arena = state.server.game.arena();
 
         const ENTITY_DAMAGE_ANIMATION_TIME: Duration = Duration::from_millis(66);
@@ -72,6 +117,15 @@ impl Arena {
             }
         }
         self.previous_entities = arena.entities.clone();
+
+        // Camera
+        let map_size = arena.size;
+        self.camera.resize(ArenaWidget::map_view_size(self.display_size), map_size);
+
+        let player = &state.server.game.players[arena.user_player.player_id];
+        if let Some(entity) = arena.entities.get(&player.entity_id) {
+            self.camera.follow(entity.position, map_size);
+        }
     }
 }
 
@@ -82,21 +136,27 @@ pub struct ArenaWidget<'a> {
 }
 
 impl<'a> ArenaWidget<'a> {
-    pub fn dimension(state: &State) -> (u16, u16) {
-        let map_size = state.server.game_info.as_ref().unwrap().map_size as u16;
-        let map_dim = MapWidget::dimension(map_size);
-
-        (
-            PlayerPanelListWidget::WIDTH + 1 + map_dim.0,
-            1 + ArenaInfoLabelWidget::HEIGHT + map_dim.1 + NotificationLabelWidget::HEIGHT,
-        )
+    const SIDE_PANELS_WIDTH: u16 = PlayerPanelListWidget::WIDTH + 1 + 1 + ChatPanelWidget::WIDTH;
+    const LABELS_HEIGHT: u16 = 1 + ArenaInfoLabelWidget::HEIGHT + NotificationLabelWidget::HEIGHT;
+
+    /// Number of map positions that fit in the display, inside the map border.
+    fn map_view_size(display_size: (u16, u16)) -> Vec2 {
+        let width = display_size.0.saturating_sub(Self::SIDE_PANELS_WIDTH + 2);
+        let height = display_size.1.saturating_sub(Self::LABELS_HEIGHT + 2);
+        Vec2::xy((width as i32 + 1) / 2, height as i32)
+    }
+
+    pub fn dimension(arena: &Arena) -> (u16, u16) {
+        let map_dim = MapWidget::dimension(arena.camera.size());
+
+        (Self::SIDE_PANELS_WIDTH + map_dim.0, Self::LABELS_HEIGHT + map_dim.1)
     }
 }
 
-impl Widget for ArenaWidget<'_> {
-    fn render(self, area: Rect, buffer: &mut Buffer) {
-        let map_size = self.state.server.game_info.as_ref().unwrap().map_size as u16;
-        let map_dim = MapWidget::dimension(map_size);
+impl StatefulWidget for ArenaWidget<'_> {
+    type State = Cursor;
+    fn render(self, area: Rect, buffer: &mut Buffer, cursor: &mut Cursor) {
+        let map_dim = MapWidget::dimension(self.arena.camera.size());
 
         let column = Layout::default()
             .direction(Dir::Vertical)
@@ -120,6 +180,8 @@ impl Widget for ArenaWidget<'_> {
                     Constraint::Length(PlayerPanelListWidget::WIDTH),
                     Constraint::Length(1), //Margin
                     Constraint::Length(map_dim.0),
+                    Constraint::Length(1), //Margin
+                    Constraint::Length(ChatPanelWidget::WIDTH),
                 ]
                 .as_ref(),
             )
@@ -129,7 +191,14 @@ impl Widget for ArenaWidget<'_> {
 
         MapWidget::new(self.state, self.arena).render(row[2], buffer);
 
+        ChatPanelWidget::new(self.state, &self.arena.chat).render(row[4], buffer, cursor);
+
         NotificationLabelWidget::new(self.state).render(column[3], buffer);
+
+        if let Some(page) = self.arena.scoreboard_page {
+            Clear.render(column[2], buffer);
+            ScoreboardWidget::new(self.state, page).render(column[2], buffer);
+        }
     }
 }
 
@@ -140,6 +209,7 @@ struct ArenaInfoLabelWidget<'a> {
 
 impl ArenaInfoLabelWidget<'_> {
     pub const HEIGHT: u16 = 1;
+    const LOW_TIME_SECS: u64 = 10;
 }
 
 impl Widget for ArenaInfoLabelWidget<'_> {
@@ -147,12 +217,47 @@ impl Widget for ArenaInfoLabelWidget<'_> {
         let points = self.state.server.game_info.as_ref().unwrap().winner_points;
         let number = self.state.server.game.arena_number;
 
-        let title = Spans::from(vec![
+        let mut title = vec![
             Span::raw("Arena "),
             Span::styled(number.to_string(), Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" · Points to win: "),
-            Span::styled(points.to_string(), Style::default().add_modifier(Modifier::BOLD)),
-        ]);
+        ];
+
+        match self.state.server.game.finish_timestamp {
+            Some(timestamp) => {
+                let remaining = timestamp.saturating_duration_since(Instant::now());
+                title.push(Span::raw(" · Time left: "));
+                title.push(Span::styled(
+                    util::format_duration(remaining),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+            }
+            None => {
+                title.push(Span::raw(" · Points to win: "));
+                title.push(Span::styled(
+                    points.to_string(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+            }
+        }
+
+        if let Some(timestamp) = self.state.server.game.arena().finish_timestamp {
+            let remaining = timestamp.saturating_duration_since(Instant::now());
+            let style = match remaining.as_secs() < Self::LOW_TIME_SECS {
+                true => Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                false => Style::default().add_modifier(Modifier::BOLD),
+            };
+            title.push(Span::raw(" · Round: "));
+            title.push(Span::styled(util::format_duration(remaining), style));
+        }
+
+        for (team, points) in self.state.server.game.team_points.iter().enumerate() {
+            let team_style = Style::default().fg(util::team_color(team));
+            title.push(Span::raw(" · "));
+            title.push(Span::styled(format!("{} ", util::team_name(team)), team_style));
+            title.push(Span::styled(points.to_string(), team_style.add_modifier(Modifier::BOLD)));
+        }
+
+        let title = Spans::from(title);
 
         Paragraph::new(title).alignment(Alignment::Center).render(area, buffer);
     }
@@ -174,17 +279,66 @@ impl Widget for PlayerPanelListWidget<'_> {
         let mut constraints = vec![Constraint::Length(1)]; //Top margin
         constraints
             .extend(players.iter().map(|_| Constraint::Length(PlayerPanelWidget::DIMENSION.1)));
-        constraints.push(Constraint::Min(0)); // Bottom margin
+        constraints.push(Constraint::Length(1)); // Margin
+        constraints.push(Constraint::Min(0)); // Kill feed
 
         let row = Layout::default().direction(Dir::Vertical).constraints(constraints).split(area);
 
         for (index, player) in self.state.server.game.players.iter().enumerate() {
             let character = &self.state.server.game.characters[&player.character_id];
-            let entity = self.state.server.game.arena().entities.get(&player.entity_id);
+            let data = self.state.server.game.arena().players.get(index);
 
-            PlayerPanelWidget::new(self.state, player, character, entity)
+            PlayerPanelWidget::new(self.state, player, character, data)
                 .render(row[index + 1], buffer)
         }
+
+        KillFeedWidget::new(self.state).render(row[players.len() + 2], buffer);
+    }
+}
+
+/// Last kills of the game, the newest at the bottom.
+#[derive(derive_new::new)]
+struct KillFeedWidget<'a> {
+    state: &'a State,
+}
+
+impl KillFeedWidget<'_> {
+    const KILL_DURATION: Duration = Duration::from_secs(10);
+}
+
+impl Widget for KillFeedWidget<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let game = &self.state.server.game;
+        let player_span = |id: usize| {
+            let player = &game.players[id];
+            let color = player.team.map(util::team_color).unwrap_or(Color::White);
+            Span::styled(player.name.as_str(), Style::default().fg(color))
+        };
+        let cause_style = Style::default().fg(Color::DarkGray);
+
+        let now = Instant::now();
+        let lines = game
+            .kill_feed
+            .iter()
+            .filter(|kill| now - kill.time < Self::KILL_DURATION)
+            .map(|kill| match kill.cause {
+                KillCause::Player(killer) => Spans::from(vec![
+                    player_span(killer),
+                    Span::styled(" ✕ ", Style::default().fg(Color::LightRed)),
+                    player_span(kill.victim),
+                ]),
+                KillCause::Oneself => Spans::from(vec![
+                    player_span(kill.victim),
+                    Span::styled(" killed itself", cause_style),
+                ]),
+                KillCause::Environment => {
+                    Spans::from(vec![player_span(kill.victim), Span::styled(" died", cause_style)])
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let visible_lines = lines[lines.len().saturating_sub(area.height as usize)..].to_vec();
+        Paragraph::new(visible_lines).render(area, buffer);
     }
 }
 
@@ -193,7 +347,7 @@ struct PlayerPanelWidget<'a> {
     state: &'a State,
     player: &'a Player,
     character: &'a Character,
-    entity: Option<&'a EntityData>,
+    data: Option<&'a PlayerData>,
 }
 
 impl<'a> PlayerPanelWidget<'a> {
@@ -203,16 +357,12 @@ impl<'a> PlayerPanelWidget<'a> {
 impl Widget for PlayerPanelWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
         let is_user = self.player.id == self.state.server.game.arena().user_player.player_id;
+        let is_alive = self.data.map(|data| data.health > 0).unwrap_or(false);
         let box_border_style = match is_user {
             true => Style::default()
-                .fg(if self.entity.is_some() { Color::White } else { Color::DarkGray })
+                .fg(if is_alive { Color::White } else { Color::DarkGray })
                 .add_modifier(Modifier::BOLD),
-            false => Style::default().fg(if self.entity.is_some() {
-                Color::Gray
-            }
-            else {
-                Color::DarkGray
-            }),
+            false => Style::default().fg(if is_alive { Color::Gray } else { Color::DarkGray }),
         };
 
         // Symbol panel
@@ -223,7 +373,7 @@ impl Widget for PlayerPanelWidget<'_> {
             .border_type(BorderType::Rounded)
             .render(symbol_area, buffer);
 
-        let player_color = Color::White;
+        let player_color = self.player.team.map(util::team_color).unwrap_or(Color::White);
         let player_style = match is_user {
             true => Style::default().fg(player_color).add_modifier(Modifier::BOLD),
             false => Style::default().fg(player_color),
@@ -236,11 +386,14 @@ impl Widget for PlayerPanelWidget<'_> {
         let panel_area = Rect::new(symbol_area.right(), area.y, 22, 4).intersection(area);
         let points = self.player.points;
         let points_style = Style::default().fg(Color::White);
+        let kills_deaths = format!("{}/{}", self.player.kills, self.player.deaths);
         Block::default()
             .title(Spans::from(vec![
                 Span::raw("──"),
                 Span::styled(" Pts: ", points_style),
                 Span::styled(points.to_string(), points_style),
+                Span::styled(" K/D ", Style::default().fg(Color::Gray)),
+                Span::styled(kills_deaths, Style::default().fg(Color::Gray)),
                 Span::raw(" "),
             ]))
             .borders(Borders::ALL)
@@ -252,11 +405,11 @@ impl Widget for PlayerPanelWidget<'_> {
         let content = panel_area.inner(&Margin { vertical: 1, horizontal: 1 });
 
         let bar_area = Rect::new(content.x, content.y, content.width, 1).intersection(area);
-        let health = self.entity.map(|e| e.health).unwrap_or(0);
+        let health = self.data.map(|data| data.health).unwrap_or(0);
         BarWidget::new(health, self.character.max_health(), Color::Green).render(bar_area, buffer);
 
         let bar_area = Rect::new(content.x, content.y + 1, content.width, 1).intersection(area);
-        let energy = self.entity.map(|e| e.energy).unwrap_or(0);
+        let energy = self.data.map(|data| data.energy).unwrap_or(0);
         BarWidget::new(energy, self.character.max_energy(), Color::Cyan).render(bar_area, buffer);
 
         // Bottom
@@ -270,6 +423,10 @@ impl Widget for PlayerPanelWidget<'_> {
 
         buffer.set_string(bottom.x, bottom.y, ">", arrow);
         buffer.set_string(bottom.right() - 1, bottom.y, "<", arrow);
+
+        let name_area = bottom.inner(&Margin { vertical: 0, horizontal: 2 });
+        let name = Span::styled(self.player.name.as_str(), player_style);
+        Paragraph::new(Spans::from(name)).alignment(Alignment::Center).render(name_area, buffer);
     }
 }
 
@@ -328,81 +485,266 @@ struct MapWidget<'a> {
 }
 
 impl MapWidget<'_> {
-    pub fn dimension(map_size: u16) -> (u16, u16) {
-        (map_size * 2 - 1, map_size)
+    /// Size of the view with its border.
+    pub fn dimension(view_size: Vec2) -> (u16, u16) {
+        (view_size.x as u16 * 2 - 1 + 2, view_size.y as u16 + 2)
+    }
+
+    /// Screen position of a map position, if it is inside the view.
+    fn screen_position(&self, area: Rect, position: Vec2) -> Option<(u16, u16)> {
+        match self.arena.camera.contains(position) {
+            true => {
+                let relative = position - self.arena.camera.origin();
+                Some((area.x + relative.x as u16 * 2, area.y + relative.y as u16))
+            }
+            false => None,
+        }
     }
 }
 
 impl Widget for MapWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
+        // Border, out of the view to not hide the map positions at its edges
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::White))
+            .border_type(BorderType::Rounded);
+        let view = block.inner(area);
+        block.render(area, buffer);
+        let area = view;
+
+        // Terrain, dimmed if it was explored but it is not visible now
+        let arena = self.state.server.game.arena();
+        let view_size = self.arena.camera.size();
+        for y in 0..view_size.y {
+            for x in 0..view_size.x {
+                let position = self.arena.camera.origin() + Vec2::xy(x, y);
+                let visible = arena.is_visible(position);
+                if !visible && !arena.is_explored(position) {
+                    continue
+                }
+
+                let (symbol, color) = match util::terrain_symbol(arena.terrain(position)) {
+                    Some((symbol, color)) if visible => (symbol, color),
+                    Some((symbol, _)) => (symbol, Color::DarkGray),
+                    None if visible => continue,
+                    None => (".", Color::DarkGray),
+                };
+                let (x, y) = self.screen_position(area, position).unwrap();
+                buffer.set_string(x, y, symbol, Style::default().fg(color));
+            }
+        }
+
         // Player sight
         let user_player = &self.state.server.game.arena().user_player;
         let player = &self.state.server.game.players[user_player.player_id];
         if let Some(entity) = &self.state.server.game.arena().entities.get(&player.entity_id) {
             let pos = entity.position + user_player.direction.to_vec2();
-            let x = pos.x as u16 * 2;
-            let y = pos.y as u16;
-            let style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::BOLD);
-            buffer.set_string(area.x + x, area.y + y, &"·", style);
+            if let Some((x, y)) = self.screen_position(area, pos) {
+                let style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::BOLD);
+                buffer.set_string(x, y, "·", style);
+            }
         }
 
-        // Border
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::White))
-            .border_type(BorderType::Rounded)
-            .render(area, buffer);
-
         // Spells
         for spell in self.state.server.game.arena().spells.values() {
-            let x = spell.position.x as u16 * 2;
-            let y = spell.position.y as u16;
-            let style = Style::default().fg(Color::Indexed(208)).remove_modifier(Modifier::BOLD);
-            buffer.set_string(area.x + x, area.y + y, "o", style);
+            if let Some((x, y)) = self.screen_position(area, spell.position) {
+                let style =
+                    Style::default().fg(Color::Indexed(208)).remove_modifier(Modifier::BOLD);
+                buffer.set_string(x, y, "o", style);
+            }
+        }
+
+        // Items
+        for item in self.state.server.game.arena().items.values() {
+            if let Some((x, y)) = self.screen_position(area, item.position) {
+                let (symbol, color) = util::item_symbol(item.kind);
+                buffer.set_string(x, y, symbol, Style::default().fg(color));
+            }
         }
 
         // Entities
         for (id, entity) in &self.state.server.game.arena().entities {
-            let x = entity.position.x as u16 * 2;
-            let y = entity.position.y as u16;
+            let (x, y) = match self.screen_position(area, entity.position) {
+                Some(screen_position) => screen_position,
+                None => continue,
+            };
             let character = self.state.server.game.characters.get(&entity.character_id).unwrap();
             let color = match self.arena.damaged_entities.get(id) {
                 Some(_) => Color::LightRed,
-                None => Color::White,
+                None => entity_color(self.state, entity),
             };
             let style = match character.id() {
                 CharacterId::Player(_) => Style::default().fg(color).add_modifier(Modifier::BOLD),
                 _ => Style::default().fg(color),
             };
-            buffer.set_string(area.x + x, area.y + y, &character.symbol().to_string(), style);
+            buffer.set_string(x, y, character.symbol().to_string(), style);
+        }
+
+        let map_size = self.state.server.game.arena().size;
+        if self.arena.minimap && !self.arena.camera.shows_all(map_size) {
+            let dimension = MinimapWidget::dimension(map_size);
+            if area.width > dimension.0 && area.height > dimension.1 {
+                let minimap_area = Rect::new(
+                    area.right() - dimension.0,
+                    area.y,
+                    dimension.0,
+                    dimension.1,
+                );
+                MinimapWidget::new(self.state, self.arena).render(minimap_area, buffer);
+            }
         }
 
         FinishGameMessageWidget::new(self.state).render(area, buffer);
     }
 }
 
+fn entity_color(state: &State, entity: &EntityData) -> Color {
+    state
+        .server
+        .game
+        .players
+        .iter()
+        .find(|player| player.character_id == entity.character_id)
+        .and_then(|player| player.team)
+        .map(util::team_color)
+        .unwrap_or(Color::White)
+}
+
+/// Reduced view of the whole map with the players and the visible area of the camera.
+#[derive(derive_new::new)]
+struct MinimapWidget<'a> {
+    state: &'a State,
+    arena: &'a Arena,
+}
+
+impl MinimapWidget<'_> {
+    const MAX_CELLS: usize = 8;
+
+    fn cells(map_size: usize) -> usize {
+        map_size.min(Self::MAX_CELLS)
+    }
+
+    pub fn dimension(map_size: usize) -> (u16, u16) {
+        let cells = Self::cells(map_size) as u16;
+        (cells * 2 - 1 + 2, cells + 2)
+    }
+
+    fn cell(position: Vec2, map_size: usize) -> (u16, u16) {
+        let cells = Self::cells(map_size) as i32;
+        let scale = |value: i32| (value * cells / map_size as i32) as u16;
+        (scale(position.x), scale(position.y))
+    }
+}
+
+impl Widget for MinimapWidget<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let arena = self.state.server.game.arena();
+        let cells = Self::cells(arena.size) as u16;
+
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::DarkGray))
+            .border_type(BorderType::Rounded)
+            .render(area, buffer);
+
+        let inner = area.inner(&Margin { vertical: 1, horizontal: 1 });
+        let blank = " ".repeat(inner.width as usize);
+        for y in 0..cells {
+            buffer.set_string(inner.x, inner.y + y, &blank, Style::default());
+        }
+
+        // Camera view
+        let origin = self.arena.camera.origin();
+        let (left, top) = Self::cell(origin, arena.size);
+        let last = origin + self.arena.camera.size() - Vec2::xy(1, 1);
+        let (right, bottom) = Self::cell(last, arena.size);
+        for y in top..=bottom {
+            for x in left..=right {
+                let style = Style::default().fg(Color::DarkGray);
+                buffer.set_string(inner.x + x * 2, inner.y + y, "·", style);
+            }
+        }
+
+        // Players
+        let user_player = &self.state.server.game.players[arena.user_player.player_id];
+        for entity in arena.entities.values() {
+            if let CharacterId::Player(_) = entity.character_id {
+                let (x, y) = Self::cell(entity.position, arena.size);
+                let style = match entity.id == user_player.entity_id {
+                    true => Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD),
+                    false => Style::default().fg(entity_color(self.state, entity)),
+                };
+                buffer.set_string(inner.x + x * 2, inner.y + y, "•", style);
+            }
+        }
+    }
+}
+
 #[derive(derive_new::new)]
 struct FinishGameMessageWidget<'a> {
     state: &'a State,
 }
 
+impl FinishGameMessageWidget<'_> {
+    /// Index of who has more points. If several have the same maximum points, no one wins.
+    fn winner(points: &[usize]) -> Option<usize> {
+        let max = points.iter().max()?;
+        match points.iter().filter(|&p| p == max).count() {
+            1 => points.iter().position(|p| p == max),
+            _ => None,
+        }
+    }
+}
+
 impl Widget for FinishGameMessageWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
         if let GameStatus::Finished = self.state.server.game.status {
-            let winner_points = self.state.server.game_info().winner_points;
-            let winner_player =
-                self.state.server.game.players.iter().find(|p| p.points >= winner_points).unwrap();
-            let winner_character = &self.state.server.game.characters[&winner_player.character_id];
+            let game = &self.state.server.game;
+
+            let winner = if game.team_points.is_empty() {
+                let player_points = game.players.iter().map(|p| p.points).collect::<Vec<_>>();
+                let winner_player = Self::winner(&player_points).map(|index| &game.players[index]);
+                winner_player.map(|player| {
+                    let style = Style::default().add_modifier(Modifier::BOLD);
+                    vec![Span::raw("Player "), Span::styled(player.name.as_str(), style)]
+                })
+            }
+            else {
+                let winner_team = Self::winner(&game.team_points);
+                winner_team.map(|team| {
+                    let style = Style::default().add_modifier(Modifier::BOLD);
+                    vec![
+                        Span::raw("Team "),
+                        Span::styled(util::team_name(team), style.fg(util::team_color(team))),
+                    ]
+                })
+            };
 
-            let message = vec![
-                Spans::from(vec![
-                    Span::raw("Player "),
+            let mut message = match winner {
+                Some(mut winner) => {
+                    winner.push(Span::raw(" wins!"));
+                    vec![Spans::from(winner), Spans::from(Span::raw(""))]
+                }
+                None => vec![Spans::from(Span::raw("Draw!")), Spans::from(Span::raw(""))],
+            };
+
+            let mut standings = self.state.server.game.players.iter().collect::<Vec<_>>();
+            standings.sort_by_key(|player| std::cmp::Reverse(player.points));
+            for (position, player) in standings.iter().enumerate() {
+                let character = &self.state.server.game.characters[&player.character_id];
+                let color = player.team.map(util::team_color).unwrap_or(Color::White);
+                message.push(Spans::from(vec![
+                    Span::raw(format!("{}. ", position + 1)),
                     Span::styled(
-                        winner_character.symbol().to_string(),
-                        Style::default().add_modifier(Modifier::BOLD),
+                        character.symbol().to_string(),
+                        Style::default().add_modifier(Modifier::BOLD).fg(color),
                     ),
-                    Span::raw(" wins!"),
-                ]),
+                    Span::raw(format!(" {:<12} {:>3} pts", player.name, player.points)),
+                ]));
+            }
+
+            message.extend(vec![
                 Spans::from(Span::raw("")),
                 Spans::from(vec![
                     Span::raw("Press"),
@@ -410,9 +752,9 @@ impl Widget for FinishGameMessageWidget<'_> {
                         " <Enter> ",
                         Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan),
                     ),
-                    Span::raw("to back to the menu"),
+                    Span::raw("to see the scoreboard"),
                 ]),
-            ];
+            ]);
 
             let height = message.len() as u16;
             Paragraph::new(message)
@@ -422,6 +764,120 @@ impl Widget for FinishGameMessageWidget<'_> {
     }
 }
 
+/// Statistics of the players at the end of the game.
+/// The first page shows the whole game and the next ones each arena.
+#[derive(derive_new::new)]
+struct ScoreboardWidget<'a> {
+    state: &'a State,
+    page: usize,
+}
+
+impl ScoreboardWidget<'_> {
+    /// Statistics of the page by player, with the number of arenas survived.
+    fn page_stats(&self) -> Vec<(PlayerStats, usize)> {
+        let match_stats = &self.state.server.game.match_stats;
+        let arenas = match self.page {
+            0 => &match_stats[..],
+            page => &match_stats[page - 1..page],
+        };
+
+        (0..self.state.server.game.players.len())
+            .map(|index| {
+                let mut total = PlayerStats::default();
+                let mut survived = 0;
+                for stats in arenas.iter().filter_map(|arena| arena.get(index)) {
+                    total.kills += stats.kills;
+                    total.deaths += stats.deaths;
+                    total.damage_dealt += stats.damage_dealt;
+                    total.damage_taken += stats.damage_taken;
+                    total.spells_cast += stats.spells_cast;
+                    total.spells_hit += stats.spells_hit;
+                    survived += (stats.deaths == 0) as usize;
+                }
+                (total, survived)
+            })
+            .collect()
+    }
+
+    fn accuracy(stats: &PlayerStats) -> String {
+        match stats.spells_cast {
+            0 => String::from("-"),
+            cast => format!("{}%", stats.spells_hit * 100 / cast),
+        }
+    }
+}
+
+impl Widget for ScoreboardWidget<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let game = &self.state.server.game;
+        let arenas = game.match_stats.len();
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+
+        let title = match self.page {
+            0 => String::from("Scoreboard · Whole game"),
+            page => format!("Scoreboard · Arena {} of {}", page, arenas),
+        };
+
+        let mut message = vec![
+            Spans::from(Span::styled(title, bold)),
+            Spans::from(Span::raw("")),
+            Spans::from(Span::styled(
+                format!(
+                    "  {:<12} {:>4} {:>3} {:>3} {:>6} {:>6} {:>5} {:>4} {:>5}",
+                    "Player", "Pts", "K", "D", "Dealt", "Taken", "Cast", "Acc", "Surv"
+                ),
+                Style::default().fg(Color::Gray),
+            )),
+        ];
+
+        let page_stats = self.page_stats();
+        let mut standings = game.players.iter().zip(page_stats.iter()).collect::<Vec<_>>();
+        standings.sort_by_key(|(player, _)| std::cmp::Reverse(player.points));
+        for (player, (stats, survived)) in standings {
+            let character = &game.characters[&player.character_id];
+            let color = player.team.map(util::team_color).unwrap_or(Color::White);
+            let points = match self.page {
+                0 => player.points.to_string(),
+                _ => String::from("-"),
+            };
+            let survived = match self.page {
+                0 => format!("{}/{}", survived, arenas),
+                _ => String::from(if *survived > 0 { "yes" } else { "no" }),
+            };
+            message.push(Spans::from(vec![
+                Span::styled(character.symbol().to_string(), bold.fg(color)),
+                Span::raw(format!(
+                    " {:<12} {:>4} {:>3} {:>3} {:>6} {:>6} {:>5} {:>4} {:>5}",
+                    player.name,
+                    points,
+                    stats.kills,
+                    stats.deaths,
+                    stats.damage_dealt,
+                    stats.damage_taken,
+                    stats.spells_cast,
+                    Self::accuracy(stats),
+                    survived,
+                )),
+            ]));
+        }
+
+        message.extend(vec![
+            Spans::from(Span::raw("")),
+            Spans::from(vec![
+                Span::styled("<Left>/<Right> ", bold.fg(Color::Cyan)),
+                Span::raw("to change the page · "),
+                Span::styled("<Enter> ", bold.fg(Color::Cyan)),
+                Span::raw("to back to the menu"),
+            ]),
+        ]);
+
+        let height = message.len() as u16;
+        Paragraph::new(message)
+            .alignment(Alignment::Center)
+            .render(util::vertically_centered(area, height), buffer);
+    }
+}
+
 #[derive(derive_new::new)]
 struct NotificationLabelWidget<'a> {
     state: &'a State,
@@ -429,11 +885,41 @@ struct NotificationLabelWidget<'a> {
 
 impl NotificationLabelWidget<'_> {
     const HEIGHT: u16 = 2;
+    const SUDDEN_DEATH_WARNING: Duration = Duration::from_secs(10);
+
+    fn sudden_death_messages(&self) -> Vec<Spans<'_>> {
+        let timestamp = match self.state.server.game.arena().sudden_death_timestamp {
+            Some(timestamp) => timestamp,
+            None => return vec![],
+        };
+
+        let now = Instant::now();
+        if now >= timestamp {
+            let style = Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD);
+            vec![Spans::from(Span::styled("Sudden death! The arena is shrinking", style))]
+        }
+        else if timestamp - now <= Self::SUDDEN_DEATH_WARNING {
+            let secs = timestamp.saturating_duration_since(now).as_secs() + 1;
+            let style = Style::default().fg(Color::LightYellow);
+            vec![Spans::from(vec![
+                Span::styled("Sudden death in ", style),
+                Span::styled(secs.to_string(), style.add_modifier(Modifier::BOLD)),
+                Span::styled("...", style),
+            ])]
+        }
+        else {
+            vec![]
+        }
+    }
 }
 
 impl Widget for NotificationLabelWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
         let messages = match self.state.server.game.next_arena_timestamp {
+            _ if self.state.server.reconnecting => {
+                let style = Style::default().fg(Color::LightYellow);
+                vec![Spans::from(Span::styled("Connection lost. Reconnecting...", style))]
+            }
             Some(timestamp) => {
                 let secs = timestamp.saturating_duration_since(Instant::now()).as_secs() + 1;
                 let winner_arena_player =
@@ -444,11 +930,18 @@ impl Widget for NotificationLabelWidget<'_> {
                 let player_style = Style::default().fg(Color::White).add_modifier(Modifier::BOLD);
                 let style = Style::default().fg(Color::LightCyan);
                 let winner_message = match winner_arena_player {
+                    Some(Player { team: Some(team), .. }) => Spans::from(vec![
+                        Span::styled("Team ", style),
+                        Span::styled(
+                            util::team_name(*team),
+                            player_style.fg(util::team_color(*team)),
+                        ),
+                        Span::styled(" survived. ", style),
+                    ]),
                     Some(player) => {
-                        let character = &self.state.server.game.characters[&player.character_id];
                         Spans::from(vec![
                             Span::styled("Player ", style),
-                            Span::styled(character.symbol().to_string(), player_style),
+                            Span::styled(player.name.as_str(), player_style),
                             Span::styled(" survived", style),
                             Span::styled(". ", style),
                         ])
@@ -465,7 +958,10 @@ impl Widget for NotificationLabelWidget<'_> {
                     ]),
                 ]
             }
-            None => vec![],
+            None => match self.state.server.game.status {
+                GameStatus::Started => self.sudden_death_messages(),
+                _ => vec![],
+            },
         };
 
         Paragraph::new(messages).alignment(Alignment::Center).render(area, buffer);
@@ -0,0 +1,69 @@
+use crate::vec2::{Vec2};
+
+/// Visible window of the map, in map positions.
+/// The camera follows a target only when it leaves the dead zone,
+/// and never shows positions out of the map.
+pub struct Camera {
+    origin: Vec2,
+    size: Vec2,
+}
+
+impl Camera {
+    /// Fraction of the view, at each side, where the target makes the camera move.
+    const DEAD_ZONE_DIVISOR: i32 = 4;
+
+    pub fn new() -> Camera {
+        Camera { origin: Vec2::zero(), size: Vec2::zero() }
+    }
+
+    /// Top left visible position.
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    /// Whether the whole map fits into the view.
+    pub fn shows_all(&self, map_size: usize) -> bool {
+        self.size.x >= map_size as i32 && self.size.y >= map_size as i32
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        let relative = position - self.origin;
+        relative.x >= 0 && relative.y >= 0 && relative.x < self.size.x && relative.y < self.size.y
+    }
+
+    /// Set the view size, limited by the map size.
+    pub fn resize(&mut self, size: Vec2, map_size: usize) {
+        let map_size = map_size as i32;
+        self.size = Vec2::xy(size.x.clamp(1, map_size), size.y.clamp(1, map_size));
+        self.clamp(map_size);
+    }
+
+    /// Move the view the minimum needed to keep the target out of the dead zone.
+    pub fn follow(&mut self, target: Vec2, map_size: usize) {
+        self.origin.x = Self::follow_axis(self.origin.x, self.size.x, target.x);
+        self.origin.y = Self::follow_axis(self.origin.y, self.size.y, target.y);
+        self.clamp(map_size as i32);
+    }
+
+    fn follow_axis(origin: i32, size: i32, target: i32) -> i32 {
+        let margin = size / Self::DEAD_ZONE_DIVISOR;
+        if target < origin + margin {
+            target - margin
+        }
+        else if target > origin + size - 1 - margin {
+            target - size + 1 + margin
+        }
+        else {
+            origin
+        }
+    }
+
+    fn clamp(&mut self, map_size: i32) {
+        self.origin.x = self.origin.x.clamp(0, map_size - self.size.x);
+        self.origin.y = self.origin.y.clamp(0, map_size - self.size.y);
+    }
+}
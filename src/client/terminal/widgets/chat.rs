@@ -0,0 +1,159 @@
+use super::util::{InputText};
+
+use crate::client::state::{State, ChatMessage};
+use crate::client::store::{Store, Action};
+use crate::client::terminal::renderer::{Cursor};
+
+use crate::util::{self};
+
+use tui::buffer::{Buffer};
+use tui::widgets::{Block, Borders, BorderType, Paragraph, Widget, StatefulWidget};
+use tui::layout::{Rect, Margin};
+use tui::style::{Style, Modifier, Color};
+use tui::text::{Span, Spans};
+
+use crossterm::event::{KeyEvent, KeyCode};
+
+pub struct Chat {
+    input: InputText,
+}
+
+impl Chat {
+    pub fn new() -> Chat {
+        Chat { input: InputText::new(None) }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.has_focus()
+    }
+
+    pub fn open(&mut self) {
+        self.input.focus(true);
+    }
+
+    pub fn close(&mut self) {
+        self.input.clear();
+        self.input.focus(false);
+    }
+
+    /// Process the key while the chat input is open.
+    pub fn key_pressed(&mut self, store: &mut Store, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Enter => {
+                let text = self.input.content().trim();
+                if util::is_valid_chat_message(text) {
+                    store.dispatch(Action::Chat(text.into()));
+                }
+                self.close();
+            }
+            KeyCode::Esc => self.close(),
+            KeyCode::Char(character) => {
+                let length = self.input.content().chars().count();
+                if character.is_ascii() && length < util::MAX_CHAT_MESSAGE_LEN {
+                    self.input.key_pressed(key_event);
+                }
+            }
+            _ => self.input.key_pressed(key_event),
+        }
+    }
+}
+
+#[derive(derive_new::new)]
+pub struct ChatPanelWidget<'a> {
+    state: &'a State,
+    chat: &'a Chat,
+}
+
+impl ChatPanelWidget<'_> {
+    pub const WIDTH: u16 = 30;
+
+    fn message_rows(message: &ChatMessage, width: usize) -> Vec<Spans<'_>> {
+        let header = format!("{}: ", message.player_name);
+        let header_len = header.chars().count();
+        let chars = header.chars().chain(message.text.chars()).collect::<Vec<_>>();
+        let text_style = match message.announcement {
+            true => Style::default().fg(Color::LightYellow),
+            false => Style::default(),
+        };
+
+        chars
+            .chunks(width.max(1))
+            .enumerate()
+            .map(|(index, row)| match index {
+                0 => {
+                    let name_len = header_len.min(row.len());
+                    Spans::from(vec![
+                        Span::styled(
+                            row[..name_len].iter().collect::<String>(),
+                            text_style.add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(row[name_len..].iter().collect::<String>(), text_style),
+                    ])
+                }
+                _ => Spans::from(Span::styled(row.iter().collect::<String>(), text_style)),
+            })
+            .collect()
+    }
+}
+
+impl StatefulWidget for ChatPanelWidget<'_> {
+    type State = Cursor;
+    fn render(self, area: Rect, buffer: &mut Buffer, cursor: &mut Cursor) {
+        let border_color = if self.chat.is_open() { Color::White } else { Color::Gray };
+        Block::default()
+            .title(Span::styled("Chat", Style::default().add_modifier(Modifier::BOLD)))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(border_color))
+            .border_type(BorderType::Rounded)
+            .render(area, buffer);
+
+        let inner = area.inner(&Margin { vertical: 1, horizontal: 1 });
+        if inner.height == 0 {
+            return
+        }
+
+        // Message log, the last messages at the bottom
+        let log_height = inner.height as usize - 1;
+        let rows = self
+            .state
+            .server
+            .chat_messages
+            .iter()
+            .flat_map(|message| Self::message_rows(message, inner.width as usize))
+            .collect::<Vec<_>>();
+
+        let visible_rows = rows[rows.len().saturating_sub(log_height)..].to_vec();
+        let log_area = Rect::new(inner.x, inner.y, inner.width, log_height as u16);
+        Paragraph::new(visible_rows).render(log_area, buffer);
+
+        // Input
+        let input_area = Rect::new(inner.x, inner.bottom() - 1, inner.width, 1);
+        let input = if self.chat.is_open() {
+            let content = self.chat.input.content();
+            let visible_len = (inner.width as usize).saturating_sub(2);
+            let skipped = content.len().saturating_sub(visible_len);
+
+            if let Some(position) = self.chat.input.cursor_position() {
+                let position = position.saturating_sub(skipped) as u16;
+                cursor.set(input_area.x + 2 + position, input_area.y);
+            }
+
+            Spans::from(vec![
+                Span::styled("> ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(&content[skipped..]),
+            ])
+        }
+        else if self.state.user.is_logged() {
+            let tab_style = Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan);
+            Spans::from(vec![
+                Span::styled("<Tab>", tab_style),
+                Span::styled(" to write", Style::default().fg(Color::DarkGray)),
+            ])
+        }
+        else {
+            Spans::from(vec![])
+        };
+
+        Paragraph::new(input).render(input_area, buffer);
+    }
+}
@@ -10,7 +10,7 @@ use crate::client::terminal::input::{InputEvent};
 use crate::client::terminal::renderer::{Cursor};
 
 use tui::buffer::{Buffer};
-use tui::widgets::{Widget, StatefulWidget};
+use tui::widgets::{StatefulWidget};
 use tui::layout::{Rect};
 
 use crossterm::event::{KeyCode, KeyModifiers};
@@ -46,7 +46,10 @@ impl Gui {
                     }
                 }
             }
-            InputEvent::ResizeDisplay(_, _) => {}
+            InputEvent::ResizeDisplay(..) => {
+                // The arena keeps the display size even if it is not the current view
+                return self.arena.process_event(store, event)
+            }
         }
 
         match self.view(store.state()) {
@@ -78,9 +81,9 @@ impl StatefulWidget for GuiWidget<'_> {
                 MenuWidget::new(self.state, &self.gui.menu).render(area, buffer, cursor)
             }
             View::Arena => {
-                let dimension = ArenaWidget::dimension(self.state);
+                let dimension = ArenaWidget::dimension(&self.gui.arena);
                 let area = util::centered_area(area, dimension);
-                ArenaWidget::new(self.state, &self.gui.arena).render(area, buffer)
+                ArenaWidget::new(self.state, &self.gui.arena).render(area, buffer, cursor)
             }
         }
     }
@@ -1,5 +1,6 @@
-use super::util::{self, InputText, InputCapitalLetter};
+use super::util::{self, InputText, InputCharacter};
 use super::waiting_room::{WaitingRoom, WaitingRoomWidget};
+use super::chat::{Chat, ChatPanelWidget};
 
 use crate::client::configuration::{Config};
 use crate::client::state::{State, VersionInfo, GameStatus};
@@ -9,7 +10,7 @@ use crate::client::terminal::input::{InputEvent};
 use crate::client::terminal::renderer::{Cursor};
 
 use crate::version::{self, Compatibility};
-use crate::message::{LoginStatus};
+use crate::message::{LoginStatus, RoomStatus, GameModeKind};
 
 use tui::buffer::{Buffer};
 use tui::widgets::{Block, Borders, BorderType, Paragraph, Widget, StatefulWidget};
@@ -24,24 +25,37 @@ use std::time::{Instant};
 
 pub struct Menu {
     server_addr_input: InputText,
-    character_symbol_input: InputCapitalLetter,
+    player_name_input: InputText,
+    character_symbol_input: InputCharacter,
+    character_symbol_selected: bool,
     waiting_room: WaitingRoom,
+    show_leaderboard: bool,
+    chat: Chat,
 }
 
 impl Menu {
+    const CHAT_HEIGHT: u16 = 6;
+
     pub fn new(config: &Config) -> Menu {
         Menu {
             server_addr_input: InputText::new(config.server_addr.map(|addr| addr.to_string())),
-            character_symbol_input: InputCapitalLetter::new(config.character),
+            player_name_input: InputText::new(config.player_name.clone()),
+            character_symbol_input: InputCharacter::new(config.character),
+            character_symbol_selected: false,
             waiting_room: WaitingRoom::new(
                 WaitingRoomPanelWidget::WIDTH - 2,
                 ServerInfoPanelWidget::HEIGHT - 2,
             ),
+            show_leaderboard: false,
+            chat: Chat::new(),
         }
     }
 
     pub fn process_event(&mut self, store: &mut Store, event: InputEvent) {
         match event {
+            InputEvent::KeyPressed(key_event) if self.chat.is_open() => {
+                self.chat.key_pressed(store, key_event);
+            }
             InputEvent::KeyPressed(key_event) => {
                 match key_event.code {
                     KeyCode::Enter => {
@@ -51,15 +65,47 @@ impl Menu {
                                 store.dispatch(Action::Connect(addr));
                             }
                         }
-                        else if self.character_symbol_input.has_focus() {
-                            if let Some(character) = self.character_symbol_input.content() {
-                                store.dispatch(Action::Login(character));
+                        else if self.player_name_input.has_focus()
+                            || self.character_symbol_input.has_focus()
+                        {
+                            let name = self.player_name_input.content();
+                            if !name.is_empty() {
+                                let character = self.character_symbol_input.content();
+                                store.dispatch(Action::Login(name.into(), character));
                             }
                         }
+                        else if self.can_be_ready(store.state()) {
+                            let ready = store.state().is_user_ready();
+                            store.dispatch(Action::Ready(!ready));
+                        }
+                    }
+                    KeyCode::Tab => {
+                        if store.state().user.is_logged() {
+                            self.chat.open();
+                        }
+                        else {
+                            self.character_symbol_selected = !self.character_symbol_selected;
+                        }
+                    }
+                    KeyCode::Char(character) if self.can_choose_team(store.state()) => {
+                        let teams = store.state().server.game_info().teams as u32;
+                        match character.to_digit(10) {
+                            Some(0) => store.dispatch(Action::ChooseTeam(None)),
+                            Some(team) if team <= teams => {
+                                store.dispatch(Action::ChooseTeam(Some(team as u8 - 1)));
+                            }
+                            _ => (),
+                        }
+                    }
+                    KeyCode::F(2) if store.state().server.game_info.is_some() => {
+                        self.show_leaderboard = !self.show_leaderboard;
+                        if self.show_leaderboard {
+                            store.dispatch(Action::QueryLeaderboard);
+                        }
                     }
                     KeyCode::Esc => {
                         if let Some(LoginStatus::Logged(..)) = store.state().user.login_status {
-                            if !store.state().server.is_full() {
+                            if store.state().server.game.status != GameStatus::Started {
                                 store.dispatch(Action::Logout);
                             }
                         }
@@ -73,28 +119,49 @@ impl Menu {
                     _ => (),
                 }
                 self.server_addr_input.key_pressed(key_event);
+                self.player_name_input.key_pressed(key_event);
                 self.character_symbol_input.key_pressed(key_event);
             }
             InputEvent::ResizeDisplay(_, _) => {}
         }
     }
 
+    fn can_be_ready(&self, state: &State) -> bool {
+        state.user.is_logged() && state.server.game.status == GameStatus::NotStarted
+    }
+
+    fn can_choose_team(&self, state: &State) -> bool {
+        state.user.is_logged()
+            && state.server.game_info().has_teams()
+            && state.server.game.status == GameStatus::NotStarted
+    }
+
     pub fn update(&mut self, state: &State) {
-        let (server_addr_focus, character_focus) = if !state.server.connection_status.is_connected()
-            || !state.server.has_compatible_version()
-        {
-            (true, false)
-        }
-        else if !state.user.is_logged() {
-            (false, true)
-        }
-        else {
-            (false, false)
-        };
+        let (server_addr_focus, name_focus, character_focus) =
+            if !state.server.connection_status.is_connected()
+                || !state.server.has_compatible_version()
+            {
+                (true, false, false)
+            }
+            else if !state.user.is_logged() {
+                (false, !self.character_symbol_selected, self.character_symbol_selected)
+            }
+            else {
+                (false, false, false)
+            };
 
+        self.player_name_input.focus(name_focus);
         self.character_symbol_input.focus(character_focus);
         self.server_addr_input.focus(server_addr_focus);
         self.waiting_room.update(state);
+
+        if state.server.game_info.is_none() {
+            self.show_leaderboard = false;
+        }
+
+        if !state.user.is_logged() {
+            self.chat.close();
+        }
     }
 }
 
@@ -112,8 +179,9 @@ impl<'a> MenuWidget<'a> {
                 + VersionPanelWidget::HEIGHT
                 + ClientInfoPanelWidget::HEIGHT
                 + ServerInfoPanelWidget::HEIGHT
+                + Menu::CHAT_HEIGHT
                 + NotificationLabelWidget::HEIGHT
-                + 5,
+                + 6,
         ) // margin sum
     }
 }
@@ -132,6 +200,8 @@ impl StatefulWidget for MenuWidget<'_> {
                     Constraint::Length(2), // Margin
                     Constraint::Length(ServerInfoPanelWidget::HEIGHT),
                     Constraint::Length(1), // Margin
+                    Constraint::Length(Menu::CHAT_HEIGHT),
+                    Constraint::Length(1), // Margin
                     Constraint::Length(NotificationLabelWidget::HEIGHT),
                 ]
                 .as_ref(),
@@ -159,9 +229,19 @@ impl StatefulWidget for MenuWidget<'_> {
 
         ServerInfoPanelWidget::new(self.state).render(row[0], buffer);
 
-        WaitingRoomPanelWidget::new(self.state, self.menu).render(row[2], buffer);
+        if self.menu.show_leaderboard {
+            LeaderboardPanelWidget::new(self.state).render(row[2], buffer);
+        }
+        else {
+            WaitingRoomPanelWidget::new(self.state, self.menu).render(row[2], buffer);
+        }
 
-        NotificationLabelWidget::new(self.state, self.menu).render(column[7], buffer);
+        if self.state.server.game_info.is_some() {
+            let chat_area = column[7].inner(&Margin { vertical: 0, horizontal: 2 });
+            ChatPanelWidget::new(self.state, &self.menu.chat).render(chat_area, buffer, cursor);
+        }
+
+        NotificationLabelWidget::new(self.state, self.menu).render(column[9], buffer);
     }
 }
 
@@ -229,7 +309,7 @@ struct ClientInfoPanelWidget<'a> {
 
 impl ClientInfoPanelWidget<'_> {
     const INITIAL_CURSOR: u16 = 17;
-    const HEIGHT: u16 = 2;
+    const HEIGHT: u16 = 3;
 }
 
 impl StatefulWidget for ClientInfoPanelWidget<'_> {
@@ -243,7 +323,9 @@ impl StatefulWidget for ClientInfoPanelWidget<'_> {
 
         ServerAddressLabelWidget::new(self.state, self.menu).render(column[0], buffer, cursor);
 
-        CharacterLabelWidget::new(self.state, self.menu).render(column[1], buffer, cursor);
+        PlayerNameLabelWidget::new(self.state, self.menu).render(column[1], buffer, cursor);
+
+        CharacterLabelWidget::new(self.state, self.menu).render(column[2], buffer, cursor);
     }
 }
 
@@ -266,6 +348,16 @@ impl StatefulWidget for ServerAddressLabelWidget<'_> {
 
         Paragraph::new(server_addrees_msg).alignment(Alignment::Left).render(area, buffer);
 
+        let kick_message =
+            self.state.server.kick_reason.as_ref().map(|reason| format!("Kicked: {}", reason));
+
+        let shutdown_message = self
+            .state
+            .server
+            .shutdown_reason
+            .as_ref()
+            .map(|reason| format!("The server has shut down: {}", reason));
+
         let (message, hint_color) = if self.menu.server_addr_input.content().is_empty() {
             ("Not connected", Color::DarkGray)
         }
@@ -276,6 +368,15 @@ impl StatefulWidget for ServerAddressLabelWidget<'_> {
                     ConnectionStatus::Connected => ("Connected", Color::LightGreen),
                     ConnectionStatus::NotConnected => ("Not connected", Color::DarkGray),
                     ConnectionStatus::NotFound => ("Server not found", Color::LightRed),
+                    ConnectionStatus::Lost if self.state.server.reconnecting => {
+                        ("Reconnecting...", Color::LightYellow)
+                    }
+                    ConnectionStatus::Lost if kick_message.is_some() => {
+                        (kick_message.as_deref().unwrap(), Color::LightRed)
+                    }
+                    ConnectionStatus::Lost if shutdown_message.is_some() => {
+                        (shutdown_message.as_deref().unwrap(), Color::LightYellow)
+                    }
                     ConnectionStatus::Lost => {
                         if !self.state.server.has_compatible_version() {
                             ("Version error", Color::LightRed)
@@ -298,27 +399,27 @@ impl StatefulWidget for ServerAddressLabelWidget<'_> {
 }
 
 #[derive(derive_new::new)]
-struct CharacterLabelWidget<'a> {
+struct PlayerNameLabelWidget<'a> {
     state: &'a State,
     menu: &'a Menu,
 }
 
-impl StatefulWidget for CharacterLabelWidget<'_> {
+impl StatefulWidget for PlayerNameLabelWidget<'_> {
     type State = Cursor;
     fn render(self, area: Rect, buffer: &mut Buffer, cursor: &mut Cursor) {
-        let character = self.menu.character_symbol_input.content().unwrap_or(' ');
+        let name = self.menu.player_name_input.content();
 
-        let character_msg = Spans::from(vec![
-            Span::raw("Character name:  "),
-            Span::styled(character.to_string(), Style::default().add_modifier(Modifier::BOLD)),
+        let name_msg = Spans::from(vec![
+            Span::raw("Player name:     "),
+            Span::styled(name, Style::default().add_modifier(Modifier::BOLD)),
         ]);
 
-        Paragraph::new(character_msg).alignment(Alignment::Left).render(area, buffer);
+        Paragraph::new(name_msg).alignment(Alignment::Left).render(area, buffer);
 
         let (status_message, status_color) = if self.state.user.is_logged() {
             ("Logged", Color::LightGreen)
         }
-        else if self.state.server.logged_players.contains(&character) {
+        else if self.state.server.logged_players.iter().any(|player| player.name == name) {
             ("Name already chosen", Color::LightRed)
         }
         else if self.state.server.is_full() {
@@ -334,6 +435,54 @@ impl StatefulWidget for CharacterLabelWidget<'_> {
         let hint = Span::styled(status_message, Style::default().fg(status_color));
         Paragraph::new(hint).alignment(Alignment::Right).render(area, buffer);
 
+        if let Some(ref pos) = self.menu.player_name_input.cursor_position() {
+            cursor.set(area.x + ClientInfoPanelWidget::INITIAL_CURSOR + *pos as u16, area.y);
+        }
+    }
+}
+
+#[derive(derive_new::new)]
+struct CharacterLabelWidget<'a> {
+    state: &'a State,
+    menu: &'a Menu,
+}
+
+impl StatefulWidget for CharacterLabelWidget<'_> {
+    type State = Cursor;
+    fn render(self, area: Rect, buffer: &mut Buffer, cursor: &mut Cursor) {
+        let character = match self.state.user.is_logged() {
+            true => self.state.user.character_symbol,
+            false => self.menu.character_symbol_input.content(),
+        };
+
+        let character_msg = Spans::from(vec![
+            Span::raw("Character:       "),
+            Span::styled(
+                character.unwrap_or(' ').to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]);
+
+        Paragraph::new(character_msg).alignment(Alignment::Left).render(area, buffer);
+
+        let (status_message, status_color) = if self.state.user.is_logged() {
+            ("Assigned", Color::LightGreen)
+        }
+        else if let Some(character) = character {
+            if self.state.server.logged_players.iter().any(|player| player.symbol == character) {
+                ("Already chosen, other will be assigned", Color::LightYellow)
+            }
+            else {
+                ("Preferred", Color::DarkGray)
+            }
+        }
+        else {
+            ("Automatic", Color::DarkGray)
+        };
+
+        let hint = Span::styled(status_message, Style::default().fg(status_color));
+        Paragraph::new(hint).alignment(Alignment::Right).render(area, buffer);
+
         if self.menu.character_symbol_input.has_focus() {
             cursor.set(area.x + ClientInfoPanelWidget::INITIAL_CURSOR, area.y);
         }
@@ -351,10 +500,20 @@ impl ServerInfoPanelWidget<'_> {
 
 impl Widget for ServerInfoPanelWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
+        let mut title =
+            vec![Span::styled("Server info", Style::default().add_modifier(Modifier::BOLD))];
+
+        if let Some(room) = &self.state.server.room {
+            if self.state.server.game_info.is_some() {
+                title.push(Span::raw(" · room "));
+                title.push(Span::styled(room, Style::default().add_modifier(Modifier::BOLD)));
+            }
+        }
+
         Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
-            .title(Span::styled("Server info", Style::default().add_modifier(Modifier::BOLD)))
+            .title(Spans::from(title))
             .render(area, buffer);
 
         let inner = area.inner(&Margin { vertical: 1, horizontal: 2 });
@@ -373,6 +532,19 @@ struct ServerInfoWithoutContentPanelWidget<'a> {
     state: &'a State,
 }
 
+impl ServerInfoWithoutContentPanelWidget<'_> {
+    fn room_error(&self) -> Option<&'static str> {
+        match self.state.server.room_status? {
+            RoomStatus::NotFound => Some("Room not found"),
+            RoomStatus::AlreadyExists => Some("Room already exists"),
+            RoomStatus::AlreadyLogged => Some("Already logged in other room"),
+            RoomStatus::InvalidSettings => Some("Invalid room settings"),
+            RoomStatus::RoomLimit => Some("Room limit reached"),
+            RoomStatus::Created | RoomStatus::Joined => None,
+        }
+    }
+}
+
 impl Widget for ServerInfoWithoutContentPanelWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
         let message =
@@ -389,6 +561,9 @@ impl Widget for ServerInfoWithoutContentPanelWidget<'_> {
                         ),
                     ])
                 }
+                else if let Some(message) = self.room_error() {
+                    Spans::from(Span::styled(message, Style::default().fg(Color::LightRed)))
+                }
                 else {
                     Spans::from(Span::styled(
                         "Loading information...",
@@ -412,7 +587,7 @@ struct ServerInfoWithContentPanelWidget<'a> {
 }
 
 impl ServerInfoWithContentPanelWidget<'_> {
-    const HEIGHT: u16 = 5;
+    const HEIGHT: u16 = 6;
 }
 
 impl Widget for ServerInfoWithContentPanelWidget<'_> {
@@ -431,6 +606,8 @@ impl Widget for ServerInfoWithContentPanelWidget<'_> {
         ServerInfoPointsLabelWidget::new(self.state).render(column[3], buffer);
 
         ServerInfoPlayersLabelWidget::new(self.state).render(column[4], buffer);
+
+        ServerInfoPlayerNamesLabelWidget::new(self.state).render(column[5], buffer);
     }
 }
 
@@ -510,6 +687,16 @@ impl Widget for ServerInfoMapSizeLabelWidget<'_> {
         ]);
 
         Paragraph::new(left).alignment(Alignment::Left).render(area, buffer);
+
+        let mode = match (game_info.has_teams(), game_info.friendly_fire) {
+            (true, true) => format!("{} teams · friendly fire", game_info.teams),
+            (true, false) => format!("{} teams", game_info.teams),
+            (false, _) => "Free for all".into(),
+        };
+
+        let right = Span::styled(mode, Style::default().fg(Color::Gray));
+
+        Paragraph::new(right).alignment(Alignment::Right).render(area, buffer);
     }
 }
 
@@ -528,6 +715,24 @@ impl Widget for ServerInfoPointsLabelWidget<'_> {
         ]);
 
         Paragraph::new(left).alignment(Alignment::Left).render(area, buffer);
+
+        let mode = util::game_mode_name(game_info.mode);
+        let mode = match game_info.mode {
+            GameModeKind::Timed => {
+                format!("{} ({})", mode, util::format_duration(game_info.game_duration))
+            }
+            _ => mode.into(),
+        };
+        let mode = match game_info.sudden_death {
+            Some(sudden_death) => {
+                format!("{} · Sudden death {}", mode, util::format_duration(sudden_death))
+            }
+            None => mode,
+        };
+
+        let right = Span::styled(mode, Style::default().fg(Color::Gray));
+
+        Paragraph::new(right).alignment(Alignment::Right).render(area, buffer);
     }
 }
 
@@ -539,9 +744,11 @@ struct ServerInfoPlayersLabelWidget<'a> {
 impl Widget for ServerInfoPlayersLabelWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
         let game_info = self.state.server.game_info.as_ref().unwrap();
-        let current_players_number = self.state.server.logged_players.len();
+        let logged_players = &self.state.server.logged_players;
+        let current_players_number = logged_players.len();
+        let ready_players_number = logged_players.iter().filter(|player| player.ready).count();
 
-        let players_ratio = format!("{}/{}", current_players_number, game_info.players_number);
+        let players_ratio = format!("{}/{}", current_players_number, game_info.max_players);
         let left = Spans::from(vec![
             Span::raw("Players:  "),
             Span::styled(players_ratio, Style::default().add_modifier(Modifier::BOLD)),
@@ -549,17 +756,26 @@ impl Widget for ServerInfoPlayersLabelWidget<'_> {
 
         Paragraph::new(left).alignment(Alignment::Left).render(area, buffer);
 
-        let (status_message, status_color) = if current_players_number == game_info.players_number {
-            if self.state.user.is_logged() {
-                ("Ready", Color::LightGreen)
+        let (status_message, status_color) =
+            if let Some(timestamp) = self.state.server.countdown_timestamp {
+                let seconds = timestamp.saturating_duration_since(Instant::now()).as_secs() + 1;
+                (format!("Starting game in {}...", seconds), Color::LightGreen)
             }
-            else {
-                ("Completed", Color::LightRed)
+            else if logged_players.iter().any(|player| player.playing) {
+                match game_info.late_join && self.state.is_user_ready() {
+                    true => ("Joining at the next arena".into(), Color::LightGreen),
+                    false => ("Game in progress".into(), Color::LightYellow),
+                }
             }
-        }
-        else {
-            ("Waiting other players...", Color::LightYellow)
-        };
+            else if current_players_number == game_info.max_players
+                && !self.state.user.is_logged()
+            {
+                ("Completed".into(), Color::LightRed)
+            }
+            else {
+                let ready_ratio = format!("{}/{}", ready_players_number, game_info.min_players);
+                (format!("Ready players: {}", ready_ratio), Color::LightYellow)
+            };
 
         let right = Span::styled(status_message, Style::default().fg(status_color));
 
@@ -567,6 +783,37 @@ impl Widget for ServerInfoPlayersLabelWidget<'_> {
     }
 }
 
+#[derive(derive_new::new)]
+struct ServerInfoPlayerNamesLabelWidget<'a> {
+    state: &'a State,
+}
+
+impl Widget for ServerInfoPlayerNamesLabelWidget<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        let mut names = vec![Span::raw("Logged:   ")];
+        for (index, player) in self.state.server.logged_players.iter().enumerate() {
+            if index > 0 {
+                names.push(Span::raw(" · "));
+            }
+            let style = match player.team {
+                Some(team) => Style::default().fg(util::team_color(team as usize)),
+                None => Style::default(),
+            };
+            names.push(Span::styled(player.symbol.to_string(), style.add_modifier(Modifier::BOLD)));
+            names.push(Span::styled(format!(" {}", player.name), style));
+            if player.ready {
+                names.push(Span::styled(" ✓", Style::default().fg(Color::LightGreen)));
+            }
+            names.push(Span::styled(
+                format!(" ({})", player.rating),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+
+        Paragraph::new(Spans::from(names)).alignment(Alignment::Left).render(area, buffer);
+    }
+}
+
 #[derive(derive_new::new)]
 struct WaitingRoomPanelWidget<'a> {
     state: &'a State,
@@ -598,6 +845,64 @@ impl Widget for WaitingRoomPanelWidget<'_> {
     }
 }
 
+#[derive(derive_new::new)]
+struct LeaderboardPanelWidget<'a> {
+    state: &'a State,
+}
+
+impl Widget for LeaderboardPanelWidget<'_> {
+    fn render(self, area: Rect, buffer: &mut Buffer) {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(Span::styled("Leaderboard", Style::default().add_modifier(Modifier::BOLD)))
+            .render(area, buffer);
+
+        let inner = area.inner(&Margin { vertical: 1, horizontal: 1 });
+
+        let leaderboard = match &self.state.server.leaderboard {
+            Some(leaderboard) => leaderboard,
+            None => {
+                let message = Span::styled("Loading...", Style::default().fg(Color::Gray));
+                return Paragraph::new(message)
+                    .alignment(Alignment::Center)
+                    .render(util::vertically_centered(inner, 1), buffer)
+            }
+        };
+
+        if leaderboard.is_empty() {
+            let message = Span::styled("No games played", Style::default().fg(Color::Gray));
+            return Paragraph::new(message)
+                .alignment(Alignment::Center)
+                .render(util::vertically_centered(inner, 1), buffer)
+        }
+
+        let name_width = (inner.width as usize).saturating_sub(8);
+        let lines = leaderboard
+            .iter()
+            .take(inner.height as usize)
+            .enumerate()
+            .map(|(position, entry)| {
+                let style = match self.state.user.player_name.as_ref() == Some(&entry.name) {
+                    true => Style::default().fg(Color::LightCyan),
+                    false => Style::default(),
+                };
+                let name = entry.name.chars().take(name_width).collect::<String>();
+                Spans::from(vec![
+                    Span::styled(format!("{:>2} ", position + 1), Style::default().fg(Color::Gray)),
+                    Span::styled(format!("{:<width$}", name, width = name_width), style),
+                    Span::styled(
+                        format!("{:>5}", entry.rating),
+                        style.add_modifier(Modifier::BOLD),
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        Paragraph::new(lines).alignment(Alignment::Left).render(inner, buffer);
+    }
+}
+
 #[derive(derive_new::new)]
 struct NotificationLabelWidget<'a> {
     state: &'a State,
@@ -620,6 +925,11 @@ impl Widget for NotificationLabelWidget<'_> {
             Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow),
         );
 
+        let f2 = Span::styled(
+            " <F2> ",
+            Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan),
+        );
+
         let messages = if !self.state.server.is_connected()
             || !self.state.server.has_compatible_version()
         {
@@ -629,21 +939,34 @@ impl Widget for NotificationLabelWidget<'_> {
             ]
         }
         else if !self.state.user.is_logged() {
+            let tab = Span::styled(
+                " <Tab> ",
+                Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan),
+            );
+
             vec![
-                if self.menu.character_symbol_input.content().is_none() {
-                    Spans::from(vec![Span::raw("Choose a character (an ascii uppercase letter)")])
+                if self.menu.player_name_input.content().is_empty() {
+                    Spans::from(vec![
+                        Span::raw("Choose a player name. Press"),
+                        tab,
+                        Span::raw("to choose a character"),
+                    ])
                 }
                 else {
                     Spans::from(vec![
                         Span::raw("Press"),
                         enter,
-                        Span::raw("to login with the character"),
+                        Span::raw("to login or"),
+                        tab,
+                        Span::raw("to choose a character"),
                     ])
                 },
                 Spans::from(vec![
                     Span::raw("Press"),
                     esc,
-                    Span::raw("to disconnect from the server"),
+                    Span::raw("to disconnect from the server or"),
+                    f2,
+                    Span::raw("for the leaderboard"),
                 ]),
             ]
         }
@@ -663,8 +986,47 @@ impl Widget for NotificationLabelWidget<'_> {
                 Span::styled("...", style),
             ])]
         }
+        else if self.state.server.game_info().has_teams() {
+            let teams = self.state.server.game_info().teams;
+            let keys = Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan);
+            let ready = match self.state.is_user_ready() {
+                true => "to cancel the ready,",
+                false => "to be ready,",
+            };
+            vec![
+                Spans::from(vec![
+                    Span::raw("Press"),
+                    enter,
+                    Span::raw(ready),
+                    Span::styled(format!(" <1>..<{}> ", teams), keys),
+                    Span::raw("for a team or"),
+                    Span::styled(" <0> ", keys),
+                    Span::raw("for automatic"),
+                ]),
+                Spans::from(vec![
+                    Span::raw("Press"),
+                    esc,
+                    Span::raw("to logout the character or"),
+                    f2,
+                    Span::raw("for the leaderboard"),
+                ]),
+            ]
+        }
         else {
-            vec![Spans::from(vec![Span::raw("Press"), esc, Span::raw("to logout the character")])]
+            let ready = match self.state.is_user_ready() {
+                true => "to cancel the ready for the next game",
+                false => "to be ready for the next game",
+            };
+            vec![
+                Spans::from(vec![Span::raw("Press"), enter, Span::raw(ready)]),
+                Spans::from(vec![
+                    Span::raw("Press"),
+                    esc,
+                    Span::raw("to logout the character or"),
+                    f2,
+                    Span::raw("for the leaderboard"),
+                ]),
+            ]
         };
 
         Paragraph::new(messages).alignment(Alignment::Center).render(area, buffer);
@@ -1,4 +1,10 @@
 use tui::layout::{Rect};
+use tui::style::{Color};
+
+use crate::message::{GameModeKind, ItemKind, Terrain};
+use crate::util::{self};
+
+use std::time::{Duration};
 
 use crossterm::event::{KeyEvent, KeyCode};
 
@@ -19,6 +25,58 @@ pub fn vertically_centered(base: Rect, height: u16) -> Rect {
     Rect::new(base.x, y, base.width, height)
 }
 
+const TEAMS: [(&str, Color); 4] = [
+    ("Red", Color::LightRed),
+    ("Blue", Color::LightBlue),
+    ("Green", Color::LightGreen),
+    ("Yellow", Color::LightYellow),
+];
+
+pub fn team_name(team: usize) -> &'static str {
+    TEAMS[team % TEAMS.len()].0
+}
+
+pub fn team_color(team: usize) -> Color {
+    TEAMS[team % TEAMS.len()].1
+}
+
+pub fn game_mode_name(mode: GameModeKind) -> &'static str {
+    match mode {
+        GameModeKind::LastManStanding => "Last man standing",
+        GameModeKind::KillCount => "Kill count",
+        GameModeKind::Timed => "Timed",
+    }
+}
+
+/// Symbol and color used to draw a terrain in the map. The floor is not drawn.
+pub fn terrain_symbol(terrain: Terrain) -> Option<(&'static str, Color)> {
+    match terrain {
+        Terrain::Floor => None,
+        Terrain::Wall => Some(("#", Color::Gray)),
+        Terrain::BreakableWall => Some(("%", Color::Indexed(130))),
+        Terrain::Water => Some(("~", Color::Blue)),
+        Terrain::Mud => Some(("~", Color::Indexed(94))),
+        Terrain::Lava => Some(("≈", Color::Red)),
+        Terrain::Teleport => Some(("@", Color::LightMagenta)),
+    }
+}
+
+/// Symbol and color used to draw an item in the map.
+pub fn item_symbol(kind: ItemKind) -> (&'static str, Color) {
+    match kind {
+        ItemKind::HealthPotion => ("+", Color::LightRed),
+        ItemKind::EnergyCrystal => ("*", Color::LightCyan),
+        ItemKind::SpeedBoots => ("»", Color::LightYellow),
+        ItemKind::SpellUpgrade => ("^", Color::LightMagenta),
+    }
+}
+
+/// Format as minutes and seconds: `m:ss`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
 pub struct InputText {
     content: String,
     cursor: Option<usize>,
@@ -36,26 +94,18 @@ impl InputText {
                     self.content.insert(*cursor, character);
                     *cursor += 1;
                 }
-                KeyCode::Delete => {
-                    if *cursor < self.content.len() {
-                        self.content.remove(*cursor);
-                    }
+                KeyCode::Delete if *cursor < self.content.len() => {
+                    self.content.remove(*cursor);
                 }
-                KeyCode::Backspace => {
-                    if *cursor > 0 {
-                        *cursor -= 1;
-                        self.content.remove(*cursor);
-                    }
+                KeyCode::Backspace if *cursor > 0 => {
+                    *cursor -= 1;
+                    self.content.remove(*cursor);
                 }
-                KeyCode::Left => {
-                    if *cursor > 0 {
-                        *cursor -= 1;
-                    }
+                KeyCode::Left if *cursor > 0 => {
+                    *cursor -= 1;
                 }
-                KeyCode::Right => {
-                    if *cursor < self.content.len() {
-                        *cursor += 1;
-                    }
+                KeyCode::Right if *cursor < self.content.len() => {
+                    *cursor += 1;
                 }
                 KeyCode::Home => {
                     *cursor = 0;
@@ -87,28 +137,33 @@ impl InputText {
         self.content.as_str()
     }
 
+    pub fn clear(&mut self) {
+        self.content.clear();
+        if self.cursor.is_some() {
+            self.cursor = Some(0);
+        }
+    }
+
     pub fn cursor_position(&self) -> Option<usize> {
         self.cursor
     }
 }
 
-pub struct InputCapitalLetter {
+pub struct InputCharacter {
     content: Option<char>,
     focus: bool,
 }
 
-impl InputCapitalLetter {
-    pub fn new(content: Option<char>) -> InputCapitalLetter {
-        InputCapitalLetter { content, focus: false }
+impl InputCharacter {
+    pub fn new(content: Option<char>) -> InputCharacter {
+        InputCharacter { content, focus: false }
     }
 
     pub fn key_pressed(&mut self, key_event: KeyEvent) {
         if self.focus {
             match key_event.code {
-                KeyCode::Char(character) => {
-                    if character.is_ascii_alphabetic() {
-                        self.content = Some(character.to_ascii_uppercase());
-                    }
+                KeyCode::Char(character) if util::is_valid_character(character) => {
+                    self.content = Some(character);
                 }
                 KeyCode::Delete => {
                     self.content = None;
@@ -16,6 +16,7 @@ use std::collections::{HashMap, HashSet};
 use std::time::{Instant, Duration};
 
 struct PlayerState {
+    symbol: char,
     position: (u16, u16),
     direction: Direction,
     last_move: Instant,
@@ -23,7 +24,7 @@ struct PlayerState {
 
 pub struct WaitingRoom {
     dimension: (u16, u16),
-    players: HashMap<char, PlayerState>,
+    players: HashMap<String, PlayerState>,
 }
 
 impl WaitingRoom {
@@ -35,16 +36,18 @@ impl WaitingRoom {
         const MINIMAL_MOVE_TIME: Duration = Duration::from_millis(500);
         let mut rng = rand::thread_rng();
 
-        self.players.retain(|player, _| state.server.logged_players.contains(player));
-        for player in &state.server.logged_players {
-            if !self.players.contains_key(player) {
+        let logged_players = &state.server.logged_players;
+        self.players.retain(|name, _| logged_players.iter().any(|player| &player.name == name));
+        for player in logged_players {
+            if !self.players.contains_key(&player.name) {
                 let x_range = Uniform::from(0..self.dimension.0);
                 let y_range = Uniform::from(0..self.dimension.1);
                 let position = (x_range.sample(&mut rng), y_range.sample(&mut rng));
 
                 self.players.insert(
-                    *player,
+                    player.name.clone(),
                     PlayerState {
+                        symbol: player.symbol,
                         position,
                         direction: rng.gen(),
                         last_move: Instant::now() - MINIMAL_MOVE_TIME,
@@ -106,12 +109,12 @@ pub struct WaitingRoomWidget<'a> {
 
 impl Widget for WaitingRoomWidget<'_> {
     fn render(self, area: Rect, buffer: &mut Buffer) {
-        for (player, state) in &self.waiting_room.players {
+        for state in self.waiting_room.players.values() {
             let draw_at = (state.position.0 * 2, state.position.1);
             if draw_at.0 < area.width && draw_at.1 < area.height {
                 buffer
                     .get_mut(area.x + draw_at.0, area.y + draw_at.1)
-                    .set_char(*player)
+                    .set_char(state.symbol)
                     .set_style(Style::default().add_modifier(Modifier::BOLD));
             }
         }
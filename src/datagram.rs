@@ -0,0 +1,181 @@
+use crate::ids::{UdpSessionId};
+use crate::encoding::{self};
+
+use serde::{Serialize, Deserialize};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Sha256};
+
+pub const SESSION_KEY_SIZE: usize = 32;
+
+/// Secret shared by the client and the server by tcp to authenticate the udp messages.
+pub type SessionKey = [u8; SESSION_KEY_SIZE];
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Who sends the datagram.
+/// Part of the authentication, to not accept a datagram reflected to its sender.
+#[derive(Clone, Copy)]
+pub enum Sender {
+    Client,
+    Server,
+}
+
+/// Envelope of the messages sent by udp.
+/// The session is identified by a public id instead of its token,
+/// and the content is authenticated with the session key.
+#[derive(Serialize, Deserialize)]
+pub struct Datagram {
+    pub session_id: UdpSessionId,
+    pub sequence: u64,
+    pub payload: Vec<u8>,
+    mac: Vec<u8>,
+}
+
+impl Datagram {
+    fn create_mac(
+        key: &SessionKey,
+        sender: Sender,
+        session_id: UdpSessionId,
+        sequence: u64,
+        payload: &[u8],
+    ) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).unwrap();
+        mac.update(&[sender as u8]);
+        mac.update(&(session_id as u64).to_le_bytes());
+        mac.update(&sequence.to_le_bytes());
+        mac.update(payload);
+        mac
+    }
+
+    /// Creates the datagram of the payload and returns it encoded.
+    pub fn seal(
+        key: &SessionKey,
+        sender: Sender,
+        session_id: UdpSessionId,
+        sequence: u64,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mac = Self::create_mac(key, sender, session_id, sequence, payload);
+        let datagram = Datagram {
+            session_id,
+            sequence,
+            payload: payload.to_vec(),
+            mac: mac.finalize().into_bytes().to_vec(),
+        };
+        bincode::serialize(&datagram).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Datagram> {
+        encoding::decode(data)
+    }
+
+    /// Checks that the datagram was created by the sender with the key.
+    pub fn verify(&self, key: &SessionKey, sender: Sender) -> bool {
+        let mac = Self::create_mac(key, sender, self.session_id, self.sequence, &self.payload);
+        mac.verify(&self.mac).is_ok()
+    }
+}
+
+/// Registers the received sequence numbers to reject the repeated ones.
+/// Datagrams could arrive unordered, so the last `SIZE` sequence numbers are remembered.
+/// Older sequence numbers are always rejected.
+#[derive(Default)]
+pub struct ReplayWindow {
+    highest: u64,
+    received: u64, // Bit n set if 'highest - n' was received
+}
+
+impl ReplayWindow {
+    pub const SIZE: u64 = 64;
+
+    /// Greatest sequence number accepted.
+    pub fn highest(&self) -> u64 {
+        self.highest
+    }
+
+    /// Registers the sequence number. Returns `false` if it must be rejected.
+    pub fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.received = match shift < Self::SIZE {
+                true => self.received << shift,
+                false => 0,
+            };
+            self.received |= 1;
+            self.highest = sequence;
+            return true
+        }
+
+        let offset = self.highest - sequence;
+        if offset >= Self::SIZE || self.received & (1 << offset) != 0 {
+            return false
+        }
+        self.received |= 1 << offset;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u64 = ReplayWindow::SIZE;
+
+    #[test]
+    fn replay_window_rejects_duplicates() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(1));
+        assert!(!window.accept(1));
+        assert!(window.accept(2));
+        assert!(!window.accept(1));
+        assert!(!window.accept(2));
+    }
+
+    #[test]
+    fn replay_window_accepts_reordering_inside_the_window() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(window.accept(98));
+        assert!(window.accept(99));
+        assert!(!window.accept(98));
+        assert!(window.accept(100 - (SIZE - 1)));
+        assert!(!window.accept(100 - (SIZE - 1)));
+        assert_eq!(window.highest(), 100);
+    }
+
+    #[test]
+    fn replay_window_rejects_reordering_outside_the_window() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(!window.accept(100 - SIZE));
+        assert!(!window.accept(1));
+        assert_eq!(window.highest(), 100);
+    }
+
+    #[test]
+    fn replay_window_keeps_the_received_after_a_small_jump() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(window.accept(100 + SIZE - 1));
+        assert!(!window.accept(100));
+        assert!(window.accept(101));
+    }
+
+    #[test]
+    fn replay_window_forgets_the_received_after_a_jump_of_the_window_size() {
+        let mut window = ReplayWindow::default();
+        assert!(window.accept(100));
+        assert!(window.accept(99));
+        assert!(window.accept(100 + SIZE));
+        assert!(!window.accept(100));
+        assert!(!window.accept(99));
+        assert!(window.accept(101));
+        assert!(!window.accept(101));
+
+        assert!(window.accept(100 + 10 * SIZE));
+        assert!(!window.accept(100 + 10 * SIZE));
+        assert!(!window.accept(100 + SIZE));
+        assert!(window.accept(100 + 9 * SIZE + 1));
+    }
+}
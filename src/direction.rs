@@ -16,8 +16,8 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn to_vec2(&self) -> Vec2 {
-        match *self {
+    pub fn to_vec2(self) -> Vec2 {
+        match self {
             Direction::Up => Vec2::y(-1),
             Direction::Right => Vec2::x(1),
             Direction::Down => Vec2::y(1),
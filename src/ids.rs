@@ -17,8 +17,10 @@ macro_rules! define_optional_id {
 }
 
 pub type SessionToken = usize;
+pub type UdpSessionId = usize;
 
 define_optional_id!(EntityId);
 define_optional_id!(SpellId);
 define_optional_id!(SpellSpecId);
 define_optional_id!(SkillId);
+define_optional_id!(ItemId);
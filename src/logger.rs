@@ -2,8 +2,16 @@ use fern::colors::{Color, ColoredLevelConfig};
 use log::{LevelFilter};
 use colored::{Colorize};
 use clap::{crate_name};
+use serde::{Serialize};
 
 use std::str::{FromStr};
+use std::fmt::{self};
+use std::cell::{RefCell};
+use std::path::{Path, PathBuf};
+use std::io::{Write};
+use std::fs::{self, File};
+use std::collections::{HashMap};
+use std::sync::{Mutex};
 
 #[derive(PartialEq)]
 pub enum Level {
@@ -38,12 +46,119 @@ impl FromStr for Level {
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum Format {
+    Text,
+    Json, // A json object by line, with the fields of the context
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatUnknown;
+
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+impl FromStr for Format {
+    type Err = FormatUnknown;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(FormatUnknown),
+        }
+    }
+}
+
+/// Structured fields attached to the logs written by the current thread.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Context {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arena: Option<usize>,
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    pub match_id: Option<String>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+lazy_static! {
+    /// Files of the running matches, by match id.
+    static ref MATCH_LOG_FILES: Mutex<HashMap<String, File>> = Mutex::new(HashMap::new());
+}
+
+/// Runs the function with the context attached to its logs.
+pub fn with_context<R>(context: Context, function: impl FnOnce() -> R) -> R {
+    let previous = CONTEXT.with(|current| current.replace(context));
+    let result = function();
+    CONTEXT.with(|current| current.replace(previous));
+    result
+}
+
+/// Modifies the context of the function running by `with_context()`.
+pub fn update_context(update: impl FnOnce(&mut Context)) {
+    CONTEXT.with(|current| update(&mut current.borrow_mut()));
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    time: String,
+    level: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    message: String,
+    #[serde(flatten)]
+    context: Context,
+}
+
+fn json_line(message: &fmt::Arguments, record: &log::Record, with_target: bool) -> String {
+    let json_record = JsonRecord {
+        time: chrono::Local::now().to_rfc3339(),
+        level: record.level().to_string(),
+        target: if with_target { Some(record.target()) } else { None },
+        message: message.to_string(),
+        context: CONTEXT.with(|context| context.borrow().clone()),
+    };
+    serde_json::to_string(&json_record).unwrap()
+}
+
+/// Appends the already formatted record to the file of the match of the current context.
+/// The file is kept open until `close_match_log()` is called.
+fn write_match_log(directory: &Path, record: &log::Record) {
+    let match_id = match CONTEXT.with(|context| context.borrow().match_id.clone()) {
+        Some(match_id) => match_id,
+        None => return,
+    };
+
+    let mut files = MATCH_LOG_FILES.lock().unwrap();
+    if !files.contains_key(&match_id) {
+        match fern::log_file(directory.join(format!("{}.log", match_id))) {
+            Ok(file) => files.insert(match_id.clone(), file),
+            Err(_) => return,
+        };
+    }
+    writeln!(files.get_mut(&match_id).unwrap(), "{}", record.args()).ok();
+}
+
+/// Closes the log file of a finished match.
+pub fn close_match_log(match_id: &str) {
+    MATCH_LOG_FILES.lock().unwrap().remove(match_id);
+}
+
 pub enum Output<'a> {
     Stdout,
     File(&'a str),
 }
 
-pub fn init(level: Level, output: Output) {
+/// Writes the logs to the output, and also the logs of each match to its own file
+/// in the `match_logs` directory if specified.
+pub fn init(level: Level, output: Output, format: Format, match_logs: Option<PathBuf>) {
     let level_colors = ColoredLevelConfig::new()
         .error(Color::Red)
         .warn(Color::Yellow)
@@ -63,23 +178,66 @@ pub fn init(level: Level, output: Output) {
         Level::Dev => log_config.level(LevelFilter::Trace),
     };
 
-    let log_config = log_config.format(move |out, message, record| {
-        let target =
-            if level == Level::Dev { format!("[{}] ", record.target()) } else { String::new() };
-
-        out.finish(format_args!(
-            "{}{}{} {}",
-            format!("[{}] ", chrono::Local::now().format("%H:%M:%S")).white(),
-            target.white(),
-            level_colors.color(record.level()),
-            format!("{}", message).bright_white(),
-        ))
+    let with_target = level == Level::Dev;
+    let output_config = fern::Dispatch::new().format(move |out, message, record| match format {
+        Format::Text => {
+            let target =
+                if with_target { format!("[{}] ", record.target()) } else { String::new() };
+
+            out.finish(format_args!(
+                "{}{}{} {}",
+                format!("[{}] ", chrono::Local::now().format("%H:%M:%S")).white(),
+                target.white(),
+                level_colors.color(record.level()),
+                format!("{}", message).bright_white(),
+            ))
+        }
+        Format::Json => out.finish(format_args!("{}", json_line(message, record, with_target))),
     });
 
-    let log_config = match output {
-        Output::Stdout => log_config.chain(std::io::stdout()),
-        Output::File(name) => log_config.chain(fern::log_file(name).unwrap()),
+    let output_config = match output {
+        Output::Stdout => output_config.chain(std::io::stdout()),
+        Output::File(name) => output_config.chain(fern::log_file(name).unwrap()),
     };
 
+    let mut log_config = log_config.chain(output_config);
+
+    // Reported once the logger is running
+    let mut match_logs_error = None;
+    let match_logs = match_logs.filter(|directory| match fs::create_dir_all(directory) {
+        Ok(()) => true,
+        Err(error) => {
+            match_logs_error = Some(format!(
+                "Can not create the match logs directory '{}', match logs disabled: {}",
+                directory.display(),
+                error
+            ));
+            false
+        }
+    });
+
+    if let Some(directory) = match_logs {
+        let match_config = fern::Dispatch::new()
+            .filter(|_| CONTEXT.with(|context| context.borrow().match_id.is_some()))
+            .format(move |out, message, record| match format {
+                Format::Text => out.finish(format_args!(
+                    "[{}] {} {}",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    record.level(),
+                    message
+                )),
+                Format::Json => {
+                    out.finish(format_args!("{}", json_line(message, record, with_target)))
+                }
+            })
+            .chain(fern::Output::call(move |record| write_match_log(&directory, record)));
+
+        log_config = log_config.chain(match_config);
+    }
+
     log_config.apply().unwrap();
+
+    if let Some(error) = match_logs_error {
+        log::error!("{}", error);
+    }
 }
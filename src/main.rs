@@ -17,6 +17,9 @@ mod vec2;
 mod character;
 mod ids;
 mod specification;
+mod sight;
+mod datagram;
+mod challenge;
 
 use clap::{self, App, AppSettings};
 
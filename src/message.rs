@@ -2,11 +2,14 @@ use crate::version::{Compatibility};
 use crate::character::{CharacterId, Character};
 use crate::vec2::{Vec2};
 use crate::direction::{Direction};
-use crate::ids::{SessionToken, EntityId, SpellId, SpellSpecId, SkillId};
+use crate::ids::{SessionToken, UdpSessionId, EntityId, SpellId, SpellSpecId, SkillId, ItemId};
+use crate::datagram::{SessionKey};
+use crate::challenge::{Nonce};
 
 use serde::{Serialize, Deserialize};
 
 use std::time::{Duration};
+use std::str::{FromStr};
 
 // See the protocol diagram in docs/design/communication.md
 
@@ -20,15 +23,30 @@ pub enum ClientMessage {
     // Version
     Version(String),
 
+    // Rooms
+    ListRooms,
+    CreateRoom(RoomSettings),
+    JoinRoom(String),
+
     // Server info
     SubscribeServerInfo,
+    QueryLeaderboard,
 
     // Login messages
-    Login(char),
+    Login(String, Option<char>), //player name, preferred character symbol
+    Reconnect(String),       //player name of the session to reclaim
+    ReconnectProof(Vec<u8>), //answer to the reconnect challenge, HMAC(session token, nonce)
     Logout,
 
+    // Lobby
+    ChooseTeam(Option<u8>), //team, automatic if none
+    Ready(bool),            //ready to play the next game
+
+    // Chat
+    Chat(String),
+
     // Udp handshake
-    ConnectUdp(SessionToken),
+    ConnectUdp, // Only valid inside an authenticated datagram
     TrustUdp,
 
     // Arena real time messages
@@ -42,25 +60,43 @@ pub enum ServerMessage {
     // Version
     Version(String, Compatibility),
 
+    // Rooms
+    RoomList(Vec<RoomSummary>),
+    RoomStatus(String, RoomStatus), //room name, status
+
     // Server info
     StaticServerInfo(ServerInfo),
-    DynamicServerInfo(Vec<char>), //player list
+    DynamicServerInfo(Vec<LoggedPlayer>),
+    Leaderboard(Vec<RatingEntry>),
+
+    // Lobby
+    Countdown(Option<Duration>), //time until the next game, none if cancelled
 
     // Login messages
-    LoginStatus(char, LoginStatus), //player, status
+    LoginStatus(String, LoginStatus), //player name, status
+    ReconnectChallenge(Nonce),        //to prove the knowledge of the session token
+    Kicked(String),                   //reason
+    Shutdown(String, Duration),       //reason, grace time until the connection is closed
+
+    // Chat
+    Chat(String, String), //player name, text
+    Announcement(String), //text written by the server administrator
 
     // Udp handshake
+    UdpKey(UdpSessionId, SessionKey), //udp session id, secret key to authenticate the datagrams
     UdpConnected,
 
     // Game messages
     StartGame(GameInfo),
-    FinishGame,
+    PlayersJoined(GameInfo), //game with the players joined between arenas
+    FinishGame(Vec<Vec<PlayerStats>>), //stats of each arena by player index
     GameEvent(GameEvent),
     GameStep(Frame),
 
     // Arena messages
     WaitArena(Duration),
     StartArena(ArenaInfo),
+    TerrainChanged(Vec<TerrainChange>),
 }
 
 // ===================================================
@@ -74,31 +110,228 @@ pub enum LoggedKind {
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum LoginStatus {
-    Logged(SessionToken, LoggedKind),
+    Logged(Option<SessionToken>, char, LoggedKind), //token (only the first time), symbol, kind
     InvalidPlayerName,
     AlreadyLogged,
     PlayerLimit,
+    InvalidToken,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomSettings {
+    pub name: String,
+    pub min_players: u8, //ready players to start a game
+    pub max_players: u8,
+    pub map_size: u16,
+    pub winner_points: u16,
+    pub teams: u8, //0 for free for all
+    pub friendly_fire: bool,
+    pub mode: GameModeKind,
+    pub game_duration: Duration, //only for timed mode
+    pub sudden_death: Option<Duration>, //arena time until it starts to shrink
+    pub arena_time_limit: Option<Duration>,
+    pub tiebreak: Tiebreak, //only with arena time limit
+    pub late_join: Option<CatchUp>, //none if a running game can not be joined
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomSummary {
+    pub settings: RoomSettings,
+    pub logged_players: u8,
+    pub in_game: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum RoomStatus {
+    Created,
+    Joined,
+    NotFound,
+    AlreadyExists,
+    AlreadyLogged,
+    InvalidSettings,
+    RoomLimit,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ServerInfo {
+    pub room: String,
     pub udp_port: u16,
-    pub players_number: u8,
+    pub min_players: u8, //ready players to start a game
+    pub max_players: u8,
     pub map_size: u16,
     pub winner_points: u16,
-    pub logged_players: Vec<char>,
+    pub teams: u8, //0 for free for all
+    pub friendly_fire: bool,
+    pub mode: GameModeKind,
+    pub game_duration: Duration, //only for timed mode
+    pub sudden_death: Option<Duration>, //arena time until it starts to shrink
+    pub late_join: Option<CatchUp>,     //none if a running game can not be joined
+    pub countdown: Option<Duration>,    //time until the next game if it is counting down
+    pub logged_players: Vec<LoggedPlayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameModeKind {
+    LastManStanding,
+    KillCount,
+    Timed,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameModeUnknown;
+
+impl GameModeKind {
+    pub const NAMES: [&'static str; 3] = ["last-man-standing", "kill-count", "timed"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameModeKind::LastManStanding => Self::NAMES[0],
+            GameModeKind::KillCount => Self::NAMES[1],
+            GameModeKind::Timed => Self::NAMES[2],
+        }
+    }
+}
+
+impl FromStr for GameModeKind {
+    type Err = GameModeUnknown;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-man-standing" => Ok(GameModeKind::LastManStanding),
+            "kill-count" => Ok(GameModeKind::KillCount),
+            "timed" => Ok(GameModeKind::Timed),
+            _ => Err(GameModeUnknown),
+        }
+    }
+}
+
+/// Rule to choose the arena winners when the arena time is over.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Tiebreak {
+    Health,
+    Damage,
+}
+
+#[derive(Debug, Clone)]
+pub struct TiebreakUnknown;
+
+impl Tiebreak {
+    pub const NAMES: [&'static str; 2] = ["health", "damage"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tiebreak::Health => Self::NAMES[0],
+            Tiebreak::Damage => Self::NAMES[1],
+        }
+    }
+}
+
+impl FromStr for Tiebreak {
+    type Err = TiebreakUnknown;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "health" => Ok(Tiebreak::Health),
+            "damage" => Ok(Tiebreak::Damage),
+            _ => Err(TiebreakUnknown),
+        }
+    }
+}
+
+/// Rule to give the initial points of a player that joins a running game.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CatchUp {
+    Zero,
+    Lowest,
+    Average,
+}
+
+#[derive(Debug, Clone)]
+pub struct CatchUpUnknown;
+
+impl CatchUp {
+    pub const NAMES: [&'static str; 3] = ["zero", "lowest", "average"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CatchUp::Zero => Self::NAMES[0],
+            CatchUp::Lowest => Self::NAMES[1],
+            CatchUp::Average => Self::NAMES[2],
+        }
+    }
+}
+
+impl FromStr for CatchUp {
+    type Err = CatchUpUnknown;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(CatchUp::Zero),
+            "lowest" => Ok(CatchUp::Lowest),
+            "average" => Ok(CatchUp::Average),
+            _ => Err(CatchUpUnknown),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoggedPlayer {
+    pub name: String,
+    pub symbol: char,
+    pub team: Option<u8>, //chosen team
+    pub rating: u32,
+    pub ready: bool,   //for the next game
+    pub playing: bool, //in the current game
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RatingEntry {
+    pub name: String,
+    pub rating: u32,
+    pub games: usize,
+    pub wins: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GameInfo {
     pub characters: Vec<Character>,
-    pub players: Vec<(CharacterId, usize)>, //id, points
+    pub players: Vec<PlayerInfo>,
+    pub team_points: Vec<usize>, //empty for free for all
+    pub remaining_time: Option<Duration>, //only for timed mode
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlayerInfo {
+    pub name: String,
+    pub character_id: CharacterId,
+    pub team: Option<u8>,
+    pub points: usize,
+    pub kills: usize,  //during the game
+    pub deaths: usize, //during the game
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Terrain {
     Floor,
     Wall,
+    BreakableWall,
+    Water,
+    Mud,
+    Lava,
+    Teleport,
+}
+
+impl Terrain {
+    /// Walls can not be crossed and block the sight.
+    pub fn is_wall(&self) -> bool {
+        matches!(self, Terrain::Wall | Terrain::BreakableWall)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TerrainChange {
+    pub position: Vec2,
+    pub terrain: Terrain,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -106,11 +339,40 @@ pub struct ArenaInfo {
     pub number: usize,
     pub players: Vec<EntityId>, //id
     pub ground: Vec<Terrain>,
+    pub sudden_death: Option<Duration>, //time until the arena starts to shrink
+    pub time_limit: Option<Duration>,   //time until the arena finishes
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum KillCause {
+    Player(usize), //killer player index
+    Oneself,
+    Environment,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum GameEvent {
-    PlayerPointsUpdated(Vec<usize>), // Other possible game event here
+    PlayerPointsUpdated(Vec<usize>),
+    TeamPointsUpdated(Vec<usize>),
+    PlayerKilled(usize, KillCause), //victim player index, cause
+}
+
+/// Statistics of a player during an arena.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PlayerStats {
+    pub kills: usize,
+    pub deaths: usize,
+    pub damage_dealt: usize,
+    pub damage_taken: usize,
+    pub spells_cast: usize,
+    pub spells_hit: usize,
+}
+
+/// State of a player, known by everyone even out of sight.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PlayerData {
+    pub health: usize, //0 if the player is dead
+    pub energy: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -129,8 +391,34 @@ pub struct SpellData {
     pub position: Vec2,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ItemKind {
+    HealthPotion,
+    EnergyCrystal,
+    SpeedBoots,
+    SpellUpgrade,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 4] = [
+        ItemKind::HealthPotion,
+        ItemKind::EnergyCrystal,
+        ItemKind::SpeedBoots,
+        ItemKind::SpellUpgrade,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemData {
+    pub id: ItemId,
+    pub kind: ItemKind,
+    pub position: Vec2,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Frame {
+    pub players: Vec<PlayerData>, //same order as the players of the game
     pub entities: Vec<EntityData>,
     pub spells: Vec<SpellData>,
+    pub items: Vec<ItemData>,
 }
@@ -1,14 +1,26 @@
 mod server_manager;
 mod session;
+mod room;
+mod chat;
 mod game;
+mod ratings;
+mod input;
+mod admin;
+mod bans;
+mod metrics;
 
 use server_manager::{ServerManager, Config};
+use room::{RoomConfig};
+use game::{GameConfig};
 
 use crate::logger::{self};
+use crate::util::{self};
+use crate::message::{GameModeKind, Tiebreak, CatchUp};
 
 use clap::{App, Arg, ArgMatches};
 
 use std::time::{Duration};
+use std::path::{PathBuf};
 
 lazy_static! {
     static ref DEFAULT_TCP_PORT: String = 3549.to_string();
@@ -27,6 +39,20 @@ pub fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                 .possible_values(&logger::LOG_LEVELS)
                 .help("Set the log level of verbosity"),
         )
+        .arg(
+            Arg::with_name("log-format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .default_value("text")
+                .possible_values(&logger::LOG_FORMATS)
+                .help("Set the log format. The json format adds fields as the endpoint or arena"),
+        )
+        .arg(
+            Arg::with_name("match-logs")
+                .long("match-logs")
+                .value_name("DIR")
+                .help("Write also the logs of each match to its own file in this directory"),
+        )
         .arg(
             Arg::with_name("tcp-port")
                 .long("tcp-port")
@@ -55,19 +81,129 @@ pub fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                 .short("s")
                 .value_name("SIZE")
                 .default_value("20")
-                .validator(|port| match port.parse::<usize>() {
-                    Ok(_) => Ok(()),
+                .validator(|size| match size.parse::<usize>() {
+                    Ok(size) => match (RoomConfig::MIN_MAP_SIZE..=RoomConfig::MAX_MAP_SIZE)
+                        .contains(&size)
+                    {
+                        true => Ok(()),
+                        false => Err(format!(
+                            "The value must be in range {}..{}",
+                            RoomConfig::MIN_MAP_SIZE,
+                            RoomConfig::MAX_MAP_SIZE
+                        )),
+                    },
                     Err(_) => Err("The value must be a positive number".into()),
                 })
                 .help("Set the map size length"),
         )
         .arg(
-            Arg::with_name("players")
-                .long("players")
+            Arg::with_name("min-players")
+                .long("min-players")
                 .short("p")
                 .value_name("NUMBER")
-                .required(true)
-                .validator(|value| match value.parse::<u32>() {
+                .default_value("2")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(number) => match number > 0 && number <= util::character_symbols().count() {
+                        true => Ok(()),
+                        false => Err(format!(
+                            "The value must be > 0 and <= {}",
+                            util::character_symbols().count()
+                        )),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help(
+                    "Minimum number of players. \
+                The countdown of a game starts when this number of players are ready.",
+                ),
+        )
+        .arg(
+            Arg::with_name("max-players")
+                .long("max-players")
+                .value_name("NUMBER")
+                .default_value("8")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(number) => match number > 0 && number <= util::character_symbols().count() {
+                        true => Ok(()),
+                        false => Err(format!(
+                            "The value must be > 0 and <= {}",
+                            util::character_symbols().count()
+                        )),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help("Maximum number of players logged in the room"),
+        )
+        .arg(
+            Arg::with_name("countdown")
+                .long("countdown")
+                .value_name("SECONDS")
+                .default_value("5")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("The value must be a positive number".into()),
+                })
+                .help("Time since there are enough ready players until the game starts"),
+        )
+        .arg(
+            Arg::with_name("teams")
+                .long("teams")
+                .value_name("NUMBER")
+                .default_value("0")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(0) => Ok(()),
+                    Ok(number) => match (2..=RoomConfig::MAX_TEAMS).contains(&number) {
+                        true => Ok(()),
+                        false => Err(format!(
+                            "The value must be 0 or between 2 and {}",
+                            RoomConfig::MAX_TEAMS
+                        )),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help(
+                    "Number of teams. \
+                With 0 teams, every player plays for itself (free for all).",
+                ),
+        )
+        .arg(
+            Arg::with_name("friendly-fire")
+                .long("friendly-fire")
+                .help("The spells damage the players of the same team"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .short("m")
+                .value_name("MODE")
+                .default_value(GameModeKind::LastManStanding.name())
+                .possible_values(&GameModeKind::NAMES)
+                .help(
+                    "Set the game mode. \
+                'last-man-standing': the survivors earn a point for each dead player. \
+                'kill-count': the players earn a point for each kill. \
+                'timed': as 'kill-count', but the game ends when the duration is over.",
+                ),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .value_name("SECONDS")
+                .default_value("180")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(number) => match number > 0 {
+                        true => Ok(()),
+                        false => Err("The value must be > 0".into()),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help("Set the game duration in the timed mode"),
+        )
+        .arg(
+            Arg::with_name("sudden-death")
+                .long("sudden-death")
+                .value_name("SECONDS")
+                .validator(|value| match value.parse::<u64>() {
                     Ok(number) => match number > 0 {
                         true => Ok(()),
                         false => Err("The value must be > 0".into()),
@@ -75,25 +211,214 @@ pub fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                     Err(_) => Err("The value must be a number".into()),
                 })
                 .help(
-                    "Number of players. \
-                The game will not start until the number of players has been reached.",
+                    "Enable the sudden death: after these seconds of arena, \
+                the arena shrinks ring by ring turning the floor into lava.",
+                ),
+        )
+        .arg(
+            Arg::with_name("arena-time")
+                .long("arena-time")
+                .value_name("SECONDS")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(number) => match number > 0 {
+                        true => Ok(()),
+                        false => Err("The value must be > 0".into()),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help(
+                    "Set a time limit for each arena. \
+                When it is over, the players that lose the tiebreak are eliminated.",
+                ),
+        )
+        .arg(
+            Arg::with_name("tiebreak")
+                .long("tiebreak")
+                .value_name("RULE")
+                .default_value(Tiebreak::Health.name())
+                .possible_values(&Tiebreak::NAMES)
+                .help(
+                    "Set the winners of an arena when its time is over: \
+                the players with more remaining 'health' or more 'damage' dealt.",
+                ),
+        )
+        .arg(
+            Arg::with_name("late-join")
+                .long("late-join")
+                .value_name("RULE")
+                .possible_values(&CatchUp::NAMES)
+                .help(
+                    "Allow the ready players to join a running game before the next arena. \
+                They start with 'zero' points, the 'lowest' or the 'average' of the players.",
+                ),
+        )
+        .arg(
+            Arg::with_name("ratings-file")
+                .long("ratings-file")
+                .value_name("PATH")
+                .default_value("asciiarena-ratings.json")
+                .help(
+                    "File where the ratings of the players and the match results are saved. \
+                Use an empty value to keep them only in memory.",
+                ),
+        )
+        .arg(
+            Arg::with_name("ban-file")
+                .long("ban-file")
+                .value_name("PATH")
+                .default_value("asciiarena-bans.txt")
+                .help(
+                    "File with the banned ips or ip ranges (CIDR notation), one by line. \
+                The file is reloaded when it changes. \
+                Use an empty value to keep them only in memory.",
+                ),
+        )
+        .arg(
+            Arg::with_name("max-connections-per-ip")
+                .long("max-connections-per-ip")
+                .value_name("NUMBER")
+                .default_value("8")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(number) => match number > 0 {
+                        true => Ok(()),
+                        false => Err("The value must be > 0".into()),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help("Maximum number of simultaneous connections from the same ip"),
+        )
+        .arg(
+            Arg::with_name("max-subscriptions")
+                .long("max-subscriptions")
+                .value_name("NUMBER")
+                .default_value("256")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(number) => match number > 0 {
+                        true => Ok(()),
+                        false => Err("The value must be > 0".into()),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help("Maximum number of clients subscribed to the server info of any room"),
+        )
+        .arg(
+            Arg::with_name("metrics-port")
+                .long("metrics-port")
+                .value_name("PORT")
+                .validator(|port| match port.parse::<u16>() {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("The value must be in range 0..65535".into()),
+                })
+                .help("Serve prometheus metrics by http in this tcp port at '/metrics'"),
+        )
+        .arg(
+            Arg::with_name("shutdown-grace")
+                .long("shutdown-grace")
+                .value_name("SECONDS")
+                .default_value("3")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("The value must be a positive number".into()),
+                })
+                .help("Time to close the server after notifying the clients of the shutdown"),
+        )
+        .arg(
+            Arg::with_name("shutdown-wait-arena")
+                .long("shutdown-wait-arena")
+                .help("On shutdown, wait for the current arenas to finish before closing"),
+        )
+        .arg(
+            Arg::with_name("max-rooms")
+                .long("max-rooms")
+                .value_name("NUMBER")
+                .default_value("8")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(number) => match number > 0 {
+                        true => Ok(()),
+                        false => Err("The value must be > 0".into()),
+                    },
+                    Err(_) => Err("The value must be a number".into()),
+                })
+                .help(
+                    "Maximum number of simultaneous rooms, including the main room. \
+                Clients can create new rooms until this limit is reached.",
                 ),
         )
 }
 
 pub fn run(matches: &ArgMatches) {
     let level = matches.value_of("log").unwrap().parse().unwrap();
-    logger::init(level, logger::Output::Stdout);
+    let format = matches.value_of("log-format").unwrap().parse().unwrap();
+    let match_logs = matches.value_of("match-logs").map(PathBuf::from);
+    logger::init(level, logger::Output::Stdout, format, match_logs);
+
+    let game_duration = matches.value_of("duration").unwrap().parse().unwrap();
 
     let config = Config {
         tcp_port: matches.value_of("tcp-port").unwrap().parse().unwrap(),
         udp_port: matches.value_of("udp-port").unwrap().parse().unwrap(),
-        players_number: matches.value_of("players").unwrap().parse().unwrap(),
-        map_size: matches.value_of("map-size").unwrap().parse().unwrap(),
-        winner_points: 5,
+        main_room: RoomConfig {
+            min_players: matches.value_of("min-players").unwrap().parse().unwrap(),
+            max_players: matches.value_of("max-players").unwrap().parse().unwrap(),
+            late_join: matches.value_of("late-join").map(|rule| rule.parse().unwrap()),
+            game: GameConfig {
+                map_size: matches.value_of("map-size").unwrap().parse().unwrap(),
+                winner_points: 5,
+                teams: matches.value_of("teams").unwrap().parse().unwrap(),
+                friendly_fire: matches.is_present("friendly-fire"),
+                mode: matches.value_of("mode").unwrap().parse().unwrap(),
+                duration: Duration::from_secs(game_duration),
+                sudden_death: matches
+                    .value_of("sudden-death")
+                    .map(|value| Duration::from_secs(value.parse().unwrap())),
+                arena_time_limit: matches
+                    .value_of("arena-time")
+                    .map(|value| Duration::from_secs(value.parse().unwrap())),
+                tiebreak: matches.value_of("tiebreak").unwrap().parse().unwrap(),
+            },
+        },
+        max_rooms: matches.value_of("max-rooms").unwrap().parse().unwrap(),
         arena_waiting: Duration::from_secs(3),
+        lobby_countdown: Duration::from_secs(
+            matches.value_of("countdown").unwrap().parse().unwrap(),
+        ),
+        chat_max_messages: 5,
+        chat_period: Duration::from_secs(10),
+        input_move_tolerance: 4.0,
+        input_max_casts: 10,
+        input_max_strikes: 20,
+        ratings_file: matches
+            .value_of("ratings-file")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from),
+        ban_file: matches
+            .value_of("ban-file")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from),
+        max_connections_per_ip: matches
+            .value_of("max-connections-per-ip")
+            .unwrap()
+            .parse()
+            .unwrap(),
+        max_subscriptions: matches.value_of("max-subscriptions").unwrap().parse().unwrap(),
+        metrics_port: matches.value_of("metrics-port").map(|port| port.parse().unwrap()),
+        shutdown_grace: Duration::from_secs(
+            matches.value_of("shutdown-grace").unwrap().parse().unwrap(),
+        ),
+        shutdown_wait_arena: matches.is_present("shutdown-wait-arena"),
     };
 
+    let room = &config.main_room;
+    if !RoomConfig::is_valid_players(room.min_players, room.max_players) {
+        return log::error!("The minimum number of players can not be greater than the maximum")
+    }
+
+    if !RoomConfig::is_valid_teams(room.game.teams, room.min_players as usize) {
+        return log::error!(
+            "The number of teams can not be greater than the minimum number of players"
+        )
+    }
+
     if let Some(server_manager) = ServerManager::new(config) {
         server_manager.run();
     }
@@ -0,0 +1,112 @@
+use super::server_manager::{MAIN_ROOM};
+
+use std::net::{IpAddr};
+use std::str::{FromStr};
+use std::io::{self, BufRead};
+
+pub const USAGE: &str = "\
+Admin commands ([room] is the main room if not specified):
+    help                        Show this help
+    sessions                    List the sessions of each room
+    kick <symbol|ip> [room]     Close the connection of a player or of an ip
+    ban <symbol|ip> [room]      Kick and reject the future connections of the ip
+    next-arena [room]           Finish the current arena and start the next one
+    reset [room]                Finish the current game without saving the ratings
+    winner-points <n> [room]    Set the points to win the next games
+    map-size <n> [room]         Set the map size of the next games
+    announce <text>             Send a message to all the clients
+    shutdown [reason]           Notify the clients and close the server (as Ctrl-C)";
+
+/// Player of a room, or all the clients of an ip.
+#[derive(Debug, Clone)]
+pub enum Target {
+    Player(String, char), //room, character symbol
+    Ip(IpAddr),
+}
+
+/// Commands written by the server administrator in the console.
+#[derive(Debug, Clone)]
+pub enum AdminCommand {
+    Help,
+    Sessions,
+    Kick(Target),
+    Ban(Target),
+    NextArena(String),           //room
+    Reset(String),               //room
+    WinnerPoints(String, usize), //room, points
+    MapSize(String, usize),      //room, size
+    Announce(String),
+    Shutdown(Option<String>), //reason
+}
+
+impl FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<AdminCommand, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+
+        let room = |index: usize| args.get(index).copied().unwrap_or(MAIN_ROOM).to_string();
+        let target = || -> Result<Target, String> {
+            let value = args.first().ok_or("Missing the symbol or the ip")?;
+            if let Ok(ip) = value.parse() {
+                return Ok(Target::Ip(ip))
+            }
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(symbol), None) => Ok(Target::Player(room(1), symbol)),
+                _ => Err(format!("'{}' is not a character symbol or an ip", value)),
+            }
+        };
+        let number = || -> Result<usize, String> {
+            let value = args.first().ok_or("Missing the number")?;
+            value.parse().map_err(|_| format!("'{}' is not a number", value))
+        };
+
+        match name {
+            "help" => Ok(AdminCommand::Help),
+            "sessions" => Ok(AdminCommand::Sessions),
+            "kick" => Ok(AdminCommand::Kick(target()?)),
+            "ban" => Ok(AdminCommand::Ban(target()?)),
+            "next-arena" => Ok(AdminCommand::NextArena(room(0))),
+            "reset" => Ok(AdminCommand::Reset(room(0))),
+            "winner-points" => Ok(AdminCommand::WinnerPoints(room(1), number()?)),
+            "map-size" => Ok(AdminCommand::MapSize(room(1), number()?)),
+            "announce" => {
+                let text = line.trim_start().trim_start_matches("announce").trim();
+                match text.is_empty() {
+                    true => Err("Missing the announcement text".into()),
+                    false => Ok(AdminCommand::Announce(text.into())),
+                }
+            }
+            "shutdown" => {
+                let reason = line.trim_start().trim_start_matches("shutdown").trim();
+                match reason.is_empty() {
+                    true => Ok(AdminCommand::Shutdown(None)),
+                    false => Ok(AdminCommand::Shutdown(Some(reason.into()))),
+                }
+            }
+            _ => Err(format!("Unknown command '{}'. Write 'help' to see the commands", name)),
+        }
+    }
+}
+
+/// Reads the commands from the standard input until it is closed.
+pub fn read_console(on_command: impl Fn(AdminCommand)) {
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if line.trim().is_empty() {
+            continue
+        }
+
+        match line.parse() {
+            Ok(command) => on_command(command),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::net::{IpAddr};
+use std::str::{FromStr};
+use std::path::{PathBuf};
+use std::time::{SystemTime};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::fmt::{self};
+
+/// An ip, or a range of ips in CIDR notation (e.g. `192.168.0.0/16`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl IpRange {
+    fn mask(bits: u32, prefix: u32) -> u128 {
+        match prefix {
+            0 => 0,
+            _ => (u128::MAX << (128 - prefix)) >> (128 - bits),
+        }
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = Self::mask(32, self.prefix);
+                u32::from(range) as u128 & mask == u32::from(ip) as u128 & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = Self::mask(128, self.prefix);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> IpRange {
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        IpRange { addr, prefix }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<IpRange, String> {
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+
+        let addr = addr.parse::<IpAddr>().map_err(|_| format!("Invalid ip '{}'", addr))?;
+        let range = IpRange::from(addr);
+        match prefix {
+            Some(prefix) => match prefix.parse() {
+                Ok(prefix) if prefix <= range.prefix => Ok(IpRange { addr, prefix }),
+                _ => Err(format!("Invalid prefix length '{}'", prefix)),
+            },
+            None => Ok(range),
+        }
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match IpRange::from(self.addr).prefix == self.prefix {
+            true => write!(f, "{}", self.addr),
+            false => write!(f, "{}/{}", self.addr, self.prefix),
+        }
+    }
+}
+
+/// Ips and ranges of ips not allowed to connect.
+/// Persisted in a text file with an entry by line, where `#` starts a comment.
+/// The file can be edited while the server is running, see `reload_if_changed()`.
+pub struct BanList {
+    path: Option<PathBuf>, // Only in memory if none
+    modified: Option<SystemTime>,
+    ranges: Vec<IpRange>,
+}
+
+impl BanList {
+    /// Loads the list from the file, or creates an empty one if the file does not exist.
+    pub fn load(path: Option<PathBuf>) -> Option<BanList> {
+        let mut ban_list = BanList { path, modified: None, ranges: Vec::new() };
+        match ban_list.read() {
+            true => Some(ban_list),
+            false => None,
+        }
+    }
+
+    fn read(&mut self) -> bool {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return true,
+        };
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                log::error!("Can not read ban list file '{}': {}", path.display(), err);
+                return false
+            }
+        };
+
+        self.modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        self.ranges = content
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let entry = line.split('#').next().unwrap().trim();
+                match entry.is_empty() {
+                    true => None,
+                    false => match entry.parse() {
+                        Ok(range) => Some(range),
+                        Err(err) => {
+                            log::warn!("{}:{}: {}. Ignored", path.display(), index + 1, err);
+                            None
+                        }
+                    },
+                }
+            })
+            .collect();
+        true
+    }
+
+    /// Reads the file again if it was modified. Returns `true` if the list was reloaded.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return false,
+        };
+
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified {
+            return false
+        }
+
+        let reloaded = self.read();
+        if reloaded {
+            log::info!("Ban list reloaded: {} entries", self.ranges.len());
+        }
+        reloaded
+    }
+
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Adds the ip to the list, appending it to the file.
+    /// Returns `false` if it was already banned.
+    pub fn ban(&mut self, ip: IpAddr) -> bool {
+        if self.is_banned(ip) {
+            return false
+        }
+
+        let range = IpRange::from(ip);
+        self.ranges.push(range);
+
+        if let Some(path) = &self.path {
+            let result = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", range));
+
+            match result {
+                Ok(()) => {
+                    self.modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+                }
+                Err(err) => log::error!("Can not save ban list file '{}': {}", path.display(), err),
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: &str) -> IpRange {
+        value.parse().unwrap()
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn ip_range_with_prefix_0_contains_every_ip_of_its_version() {
+        assert!(range("0.0.0.0/0").contains(ip("1.2.3.4")));
+        assert!(range("10.0.0.0/0").contains(ip("255.255.255.255")));
+        assert!(range("::/0").contains(ip("2001:db8::1")));
+        assert!(range("::/0").contains(ip("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff")));
+    }
+
+    #[test]
+    fn ip_range_with_full_prefix_contains_only_its_ip() {
+        assert!(range("192.168.1.10/32").contains(ip("192.168.1.10")));
+        assert!(!range("192.168.1.10/32").contains(ip("192.168.1.11")));
+        assert!(range("192.168.1.10").contains(ip("192.168.1.10")));
+        assert!(!range("192.168.1.10").contains(ip("192.168.1.11")));
+
+        assert!(range("2001:db8::1/128").contains(ip("2001:db8::1")));
+        assert!(!range("2001:db8::1/128").contains(ip("2001:db8::2")));
+        assert!(!range("2001:db8::1").contains(ip("2001:db8::1:1")));
+    }
+
+    #[test]
+    fn ip_range_with_partial_prefix() {
+        assert!(range("10.0.0.0/8").contains(ip("10.255.0.1")));
+        assert!(!range("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(range("192.168.0.0/23").contains(ip("192.168.1.255")));
+        assert!(!range("192.168.0.0/23").contains(ip("192.168.2.0")));
+
+        assert!(range("2001:db8::/32").contains(ip("2001:db8:ffff::1")));
+        assert!(!range("2001:db8::/32").contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn ip_range_does_not_mix_ip_versions() {
+        assert!(!range("0.0.0.0/0").contains(ip("::1")));
+        assert!(!range("::/0").contains(ip("127.0.0.1")));
+        assert!(!range("127.0.0.1").contains(ip("::ffff:127.0.0.1")));
+        assert!(!range("::ffff:127.0.0.1").contains(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn ip_range_rejects_invalid_prefixes() {
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("::/129".parse::<IpRange>().is_err());
+        assert!("10.0.0.0/x".parse::<IpRange>().is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use message_io::network::{Endpoint};
+
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+
+/// Limits the number of chat messages that each client can send in a period of time.
+pub struct ChatRateLimiter {
+    max_messages: usize,
+    period: Duration,
+    history: HashMap<Endpoint, VecDeque<Instant>>,
+}
+
+impl ChatRateLimiter {
+    pub fn new(max_messages: usize, period: Duration) -> ChatRateLimiter {
+        ChatRateLimiter { max_messages, period, history: HashMap::new() }
+    }
+
+    /// Registers a new message of the endpoint.
+    /// Returns `false` if the endpoint has exceeded the limit. In this case, the message is
+    /// not registered.
+    pub fn allow(&mut self, endpoint: Endpoint) -> bool {
+        let now = Instant::now();
+        let last_messages = self.history.entry(endpoint).or_default();
+        while let Some(&time) = last_messages.front() {
+            if now.duration_since(time) < self.period {
+                break
+            }
+            last_messages.pop_front();
+        }
+
+        if last_messages.len() < self.max_messages {
+            last_messages.push_back(now);
+            return true
+        }
+        false
+    }
+
+    pub fn remove(&mut self, endpoint: Endpoint) {
+        self.history.remove(&endpoint);
+    }
+}
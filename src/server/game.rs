@@ -1,56 +1,160 @@
 pub mod player;
 pub mod arena;
+pub mod mode;
 
 use player::{Player};
 use arena::{Arena};
+use mode::{GameMode, PlayerDeath};
 
 use crate::character::{Character, CharacterId, CharacterBuilder};
-use crate::message::{Terrain};
+use crate::message::{Terrain, TerrainChange, GameModeKind, Tiebreak, CatchUp, PlayerStats};
+use crate::ids::{EntityId};
+use crate::sight::{self};
 
 use rand::seq::{IteratorRandom};
 
 use std::collections::{HashMap, BTreeMap, BTreeSet};
 use std::rc::{Rc};
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct GameConfig {
+    pub map_size: usize,
+    pub winner_points: usize,
+    pub teams: usize, // 0 for free for all
+    pub friendly_fire: bool,
+    pub mode: GameModeKind,
+    pub duration: Duration, // Only for timed modes
+    pub sudden_death: Option<Duration>, // Arena time until it starts to shrink
+    pub arena_time_limit: Option<Duration>,
+    pub tiebreak: Tiebreak, // Winners of the arena when the time limit is reached
+}
 
 pub struct Game {
-    map_size: usize,
-    winner_points: usize,
+    config: GameConfig,
+    mode: Box<dyn GameMode>,
+    start_time: Instant,
 
     arena_number: usize,
     arena: Option<Arena>,
+    arena_start_time: Instant,
+    shrunk_rings: usize,
+    arena_timed_out: bool,
+    arena_kills: HashMap<char, usize>,
+    finished_arena_stats: Vec<Vec<PlayerStats>>,
 
     characters: HashMap<CharacterId, Rc<Character>>,
 
     players: BTreeMap<char, Player>,
+    entity_players: HashMap<EntityId, char>,
+    team_points: Vec<usize>, // Empty for free for all
 }
 
 impl Game {
+    /// Time between two rings of the arena turned into lava during the sudden death.
+    pub const SUDDEN_DEATH_RING_PERIOD: Duration = Duration::from_secs(3);
+
     pub fn new(
-        map_size: usize,
-        winner_points: usize,
-        player_characters: impl Iterator<Item = char>,
+        config: GameConfig,
+        player_characters: impl Iterator<Item = (String, char, Option<usize>)>, //name, symbol, team
     ) -> Game {
+        let player_characters = player_characters.collect::<Vec<_>>();
         let characters = player_characters
-            .map(|symbol| {
-                let character = CharacterBuilder::default()
-                    .id(CharacterId::Player(symbol))
-                    .symbol(symbol)
-                    .max_health(Player::MAX_LIFE)
-                    .max_energy(Player::MAX_ENERGY)
-                    .speed_base(Player::SPEED_BASE)
-                    .build()
-                    .unwrap();
-
+            .iter()
+            .map(|(_, symbol, _)| {
+                let character = Self::create_player_character(*symbol);
                 (character.id(), Rc::new(character))
             })
             .collect::<HashMap<_, _>>();
 
-        let players = characters
-            .values()
-            .map(|character| (character.symbol(), Player::new(character.clone())))
+        let players = player_characters
+            .into_iter()
+            .map(|(name, symbol, team)| {
+                let character = characters[&CharacterId::Player(symbol)].clone();
+                (symbol, Player::new(name, character, team))
+            })
             .collect();
 
-        Game { map_size, winner_points, arena_number: 0, arena: None, players, characters }
+        Game {
+            mode: mode::create(config.mode, config.duration),
+            start_time: Instant::now(),
+            arena_number: 0,
+            arena: None,
+            arena_start_time: Instant::now(),
+            shrunk_rings: 0,
+            arena_timed_out: false,
+            arena_kills: HashMap::new(),
+            finished_arena_stats: Vec::new(),
+            players,
+            entity_players: HashMap::new(),
+            characters,
+            team_points: vec![0; config.teams],
+            config,
+        }
+    }
+
+    fn create_player_character(symbol: char) -> Character {
+        CharacterBuilder::default()
+            .id(CharacterId::Player(symbol))
+            .symbol(symbol)
+            .max_health(Player::MAX_LIFE)
+            .max_energy(Player::MAX_ENERGY)
+            .speed_base(Player::SPEED_BASE)
+            .build()
+            .unwrap()
+    }
+
+    /// Adds a player to a running game. It plays from the next arena created,
+    /// starting with the points given by the catch-up rule.
+    pub fn add_player(&mut self, name: String, symbol: char, team: Option<usize>, rule: CatchUp) {
+        let points = self.players.values().map(|player| player.points());
+        let initial_points = match rule {
+            CatchUp::Zero => 0,
+            CatchUp::Lowest => points.min().unwrap_or(0),
+            CatchUp::Average => points.sum::<usize>() / self.players.len().max(1),
+        };
+
+        let character = Rc::new(Self::create_player_character(symbol));
+        self.characters.insert(character.id(), character.clone());
+
+        let mut player = Player::new(name, character, team);
+        player.add_points(initial_points);
+        self.players.insert(symbol, player);
+
+        // Keeps the stats of the previous arenas in the order of the players list
+        let index = self.player_index(symbol);
+        for arena_stats in &mut self.finished_arena_stats {
+            arena_stats.insert(index, PlayerStats::default());
+        }
+    }
+
+    pub fn mode(&self) -> &dyn GameMode {
+        &*self.mode
+    }
+
+    pub fn winner_points(&self) -> usize {
+        self.config.winner_points
+    }
+
+    /// Remaining time of the current arena until the sudden death starts, if it is enabled.
+    pub fn remaining_sudden_death(&self) -> Option<Duration> {
+        let elapsed = self.arena_start_time.elapsed();
+        self.config.sudden_death.map(|sudden_death| sudden_death.saturating_sub(elapsed))
+    }
+
+    /// Remaining time of the current arena, if it has a time limit.
+    pub fn remaining_arena_time(&self) -> Option<Duration> {
+        let elapsed = self.arena_start_time.elapsed();
+        self.config.arena_time_limit.map(|time_limit| time_limit.saturating_sub(elapsed))
+    }
+
+    pub fn elapsed_time(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Remaining time of the game, if it has a time limit.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.mode.duration().map(|duration| duration.saturating_sub(self.elapsed_time()))
     }
 
     pub fn arena(&self) -> Option<&Arena> {
@@ -69,6 +173,19 @@ impl Game {
         &self.players
     }
 
+    /// Position of the player in the players list sent to the clients.
+    pub fn player_index(&self, character_symbol: char) -> usize {
+        self.players.keys().position(|&symbol| symbol == character_symbol).unwrap()
+    }
+
+    pub fn has_teams(&self) -> bool {
+        !self.team_points.is_empty()
+    }
+
+    pub fn team_points(&self) -> &[usize] {
+        &self.team_points
+    }
+
     pub fn arena_number(&self) -> usize {
         self.arena_number
     }
@@ -81,7 +198,13 @@ impl Game {
     }
 
     pub fn create_new_arena(&mut self) -> &Arena {
-        let mut arena = Arena::new(self.map_size);
+        if self.arena.is_some() {
+            let stats = self.arena_stats();
+            self.finished_arena_stats.push(stats);
+        }
+
+        let mut arena =
+            Arena::new(self.config.map_size, self.players.len(), self.config.friendly_fire);
         let initial_positions = arena
             .map()
             .ground()
@@ -91,33 +214,198 @@ impl Game {
             .map(|(index, _)| arena.map().position_of(index))
             .choose_multiple(&mut rand::thread_rng(), self.players.len());
 
-        for (index, player) in self.players.values_mut().enumerate() {
-            let position = initial_positions[index];
+        self.entity_players.clear();
+        for (player, position) in self.players.values_mut().zip(initial_positions) {
+            player.clear_terrain_changes();
             let character = player.character().clone();
             let entity = arena.create_entity(character, position);
+            entity.set_team(player.team());
             entity.set_behaviour(player.create_entity_behaviour(entity.id()));
+            self.entity_players.insert(entity.id(), player.character().symbol());
         }
 
         self.arena = Some(arena);
+        self.arena_start_time = Instant::now();
+        self.shrunk_rings = 0;
+        self.arena_timed_out = false;
+        self.arena_kills.clear();
         self.arena_number += 1;
         self.arena.as_ref().unwrap()
     }
 
-    pub fn step(&mut self) {
-        let living_players_before = self.living_players();
+    /// Updates the game, returning the players died during the step.
+    pub fn step(&mut self) -> Vec<PlayerDeath> {
+        self.shrink_arena();
 
-        if let Some(arena) = &mut self.arena {
-            arena.update();
+        let deaths = match &mut self.arena {
+            Some(arena) => arena.update(),
+            None => return Vec::new(),
+        };
+
+        let mut player_deaths = deaths
+            .into_iter()
+            .filter_map(|death| {
+                let victim = *self.entity_players.get(&death.entity_id)?;
+                let killer = death.killer_id.and_then(|id| self.entity_players.get(&id).copied());
+                Some(PlayerDeath { victim, killer })
+            })
+            .collect::<Vec<_>>();
+
+        player_deaths.extend(self.apply_time_limit());
+
+        for death in &player_deaths {
+            match death.killer {
+                Some(killer) if killer != death.victim => {
+                    *self.arena_kills.entry(killer).or_default() += 1;
+                }
+                _ => (),
+            }
         }
 
-        let living_players_after = self.living_players();
-        let deleted_players = living_players_before.difference(&living_players_after).count();
-        if deleted_players > 0 {
-            for symbol in living_players_after {
-                let player = self.players.get_mut(&symbol).unwrap();
-                player.add_points(deleted_players);
+        if let Some(arena) = &mut self.arena {
+            for change in arena.take_terrain_changes() {
+                for player in self.players.values_mut() {
+                    player.hide_terrain_change(change);
+                }
             }
         }
+
+        let score = self.mode.score(self, &player_deaths);
+        for (symbol, points) in score.players {
+            self.players.get_mut(&symbol).unwrap().add_points(points);
+        }
+        for (team, points) in score.teams {
+            self.team_points[team] += points;
+        }
+        player_deaths
+    }
+
+    /// Statistics of the current arena by player, in the order of the players list.
+    fn arena_stats(&self) -> Vec<PlayerStats> {
+        let arena = match &self.arena {
+            Some(arena) => arena,
+            None => return Vec::new(),
+        };
+
+        let player_entities = self
+            .entity_players
+            .iter()
+            .map(|(&entity_id, &symbol)| (symbol, entity_id))
+            .collect::<HashMap<_, _>>();
+
+        self.players
+            .values()
+            .map(|player| {
+                let symbol = player.character().symbol();
+                let entity_stats = match player_entities.get(&symbol) {
+                    Some(&entity_id) => arena.entity_stats(entity_id),
+                    None => Default::default(),
+                };
+                PlayerStats {
+                    kills: self.arena_kills.get(&symbol).copied().unwrap_or(0),
+                    deaths: (player_entities.contains_key(&symbol) && !player.is_alive()) as usize,
+                    damage_dealt: entity_stats.damage_dealt,
+                    damage_taken: entity_stats.damage_taken,
+                    spells_cast: entity_stats.spells_cast,
+                    spells_hit: entity_stats.spells_hit,
+                }
+            })
+            .collect()
+    }
+
+    /// Statistics of every arena played, including the current one.
+    pub fn match_stats(&self) -> Vec<Vec<PlayerStats>> {
+        let mut stats = self.finished_arena_stats.clone();
+        if self.arena.is_some() {
+            stats.push(self.arena_stats());
+        }
+        stats
+    }
+
+    /// When the arena time is over, the players that lose the tiebreak are eliminated.
+    /// With teams, the tiebreak is computed by team. Tied players or teams survive.
+    fn apply_time_limit(&mut self) -> Vec<PlayerDeath> {
+        let time_limit = match self.config.arena_time_limit {
+            Some(time_limit) => time_limit,
+            None => return Vec::new(),
+        };
+
+        if self.arena_timed_out || self.arena_start_time.elapsed() < time_limit {
+            return Vec::new()
+        }
+        self.arena_timed_out = true;
+
+        // A side is a team, or a player without team
+        let side_of = |player: &Player| match player.team() {
+            Some(team) => (Some(team), EntityId::NONE),
+            None => (None, player.entity_id()),
+        };
+
+        let arena = self.arena.as_ref().unwrap();
+        let mut side_values = HashMap::new();
+        for player in self.players.values().filter(|player| player.is_alive()) {
+            let entity_id = player.entity_id();
+            let value = match self.config.tiebreak {
+                Tiebreak::Health => arena.entities()[&entity_id].health(),
+                Tiebreak::Damage => arena.entity_stats(entity_id).damage_dealt,
+            };
+            *side_values.entry(side_of(player)).or_insert(0) += value;
+        }
+
+        let best = side_values.values().copied().max().unwrap_or(0);
+        let losers = self
+            .players
+            .values()
+            .filter(|player| player.is_alive() && side_values[&side_of(player)] < best)
+            .map(|player| (player.character().symbol(), player.entity_id()))
+            .collect::<Vec<_>>();
+
+        let arena = self.arena.as_mut().unwrap();
+        losers
+            .into_iter()
+            .map(|(symbol, entity_id)| {
+                arena.eliminate_entity(entity_id);
+                PlayerDeath { victim: symbol, killer: None }
+            })
+            .collect()
+    }
+
+    /// During the sudden death, the outer rings of the arena turn into lava one by one,
+    /// leaving only the center.
+    fn shrink_arena(&mut self) {
+        let (arena, sudden_death) = match (&mut self.arena, self.config.sudden_death) {
+            (Some(arena), Some(sudden_death)) => (arena, sudden_death),
+            _ => return,
+        };
+
+        let max_rings = (self.config.map_size.saturating_sub(1) / 2).saturating_sub(1);
+        let elapsed = self.arena_start_time.elapsed();
+        while self.shrunk_rings < max_rings
+            && elapsed >= sudden_death + Self::SUDDEN_DEATH_RING_PERIOD * self.shrunk_rings as u32
+        {
+            self.shrunk_rings += 1;
+            arena.turn_ring_into(self.shrunk_rings, Terrain::Lava);
+        }
+    }
+
+    /// Returns the terrain changes of the current arena seen by the player since the last call.
+    /// The changes out of its sight are kept until it sees them.
+    pub fn take_visible_terrain_changes(&mut self, character_symbol: char) -> Vec<TerrainChange> {
+        let sight = self.sight(character_symbol);
+        let size = match &self.arena {
+            Some(arena) => arena.map().size(),
+            None => return Vec::new(),
+        };
+
+        let player = match self.players.get_mut(&character_symbol) {
+            Some(player) => player,
+            None => return Vec::new(),
+        };
+
+        player.reveal_terrain_changes(|change| match &sight {
+            Some(sight) => sight[change.position.y as usize * size + change.position.x as usize],
+            None => true,
+        })
     }
 
     pub fn living_players(&self) -> BTreeSet<char> {
@@ -128,7 +416,42 @@ impl Game {
             .collect()
     }
 
+    pub fn living_teams(&self) -> BTreeSet<usize> {
+        self.players
+            .values()
+            .filter(|player| player.is_alive())
+            .filter_map(|player| player.team())
+            .collect()
+    }
+
+    /// Visible area of the arena for a player: the union of its sight and its teammates' sight.
+    /// If there is no one alive to see with, the player is a spectator and there is no limit.
+    pub fn sight(&self, character_symbol: char) -> Option<Vec<bool>> {
+        let arena = self.arena.as_ref()?;
+        let player = self.players.get(&character_symbol)?;
+        let origins = self
+            .players
+            .values()
+            .filter(|other| match player.team() {
+                Some(team) => other.team() == Some(team),
+                None => other.character().symbol() == character_symbol,
+            })
+            .filter_map(|other| arena.entities().get(&other.entity_id()))
+            .map(|entity| entity.position())
+            .collect::<Vec<_>>();
+
+        let map = arena.map();
+        match origins.is_empty() {
+            true => None,
+            false => Some(sight::visible_area(map.ground(), map.size(), &origins)),
+        }
+    }
+
+    pub fn has_arena_finished(&self) -> bool {
+        self.arena_timed_out || self.mode.has_arena_finished(self)
+    }
+
     pub fn has_finished(&self) -> bool {
-        self.players.values().any(|player| player.points() >= self.winner_points)
+        self.mode.has_finished(self)
     }
 }
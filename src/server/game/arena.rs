@@ -1,37 +1,74 @@
 pub mod entity;
 pub mod map;
 pub mod spell;
+pub mod item;
 
 use map::{Map};
 use entity::{Entity, EntityAction};
 use spell::{Spell, SpellAction};
+use item::{Item};
 
 use crate::character::{Character};
-use crate::ids::{SpellId, EntityId, SpellSpecId};
+use crate::ids::{SpellId, EntityId, SpellSpecId, ItemId};
 use crate::vec2::Vec2;
-use crate::message::Terrain;
+use crate::message::{Terrain, TerrainChange, ItemKind};
+
+use rand::seq::{IteratorRandom, SliceRandom};
 
 use std::collections::{HashMap, VecDeque};
 
-use std::time::{Instant};
+use std::time::{Instant, Duration};
 use std::rc::{Rc};
 
+/// Statistics of an entity during the arena.
+#[derive(Default, Clone)]
+pub struct EntityStats {
+    pub damage_dealt: usize,
+    pub damage_taken: usize,
+    pub spells_cast: usize,
+    pub spells_hit: usize, // Spells that damaged other entity
+}
+
+pub struct Death {
+    pub entity_id: EntityId,
+    pub killer_id: Option<EntityId>,
+}
+
 pub struct Arena {
     map: Map,
     entities: HashMap<EntityId, Entity>,
     spells: HashMap<SpellId, Spell>,
+    items: HashMap<ItemId, Item>,
+    stats: HashMap<EntityId, EntityStats>,
     last_entity_id: EntityId,
     last_spell_id: SpellId,
+    last_item_id: ItemId,
+    next_item_time: Instant,
+    next_lava_time: Instant,
+    friendly_fire: bool,
 }
 
 impl Arena {
-    pub fn new(map_size: usize) -> Arena {
+    const ITEM_SPAWN_PERIOD: Duration = Duration::from_secs(5);
+    const LAVA_PERIOD: Duration = Duration::from_millis(500);
+    const LAVA_DAMAGE: i32 = 5;
+
+    /// Number of floor positions for each item that can be in the arena at the same time.
+    const FLOOR_PER_ITEM: usize = 64;
+
+    pub fn new(map_size: usize, players: usize, friendly_fire: bool) -> Arena {
         Arena {
-            map: Map::new(map_size),
+            map: Map::new(map_size, players),
             entities: HashMap::new(),
             spells: HashMap::new(),
+            items: HashMap::new(),
+            stats: HashMap::new(),
             last_entity_id: EntityId::NONE,
             last_spell_id: SpellId::NONE,
+            last_item_id: ItemId::NONE,
+            next_item_time: Instant::now() + Self::ITEM_SPAWN_PERIOD,
+            next_lava_time: Instant::now() + Self::LAVA_PERIOD,
+            friendly_fire,
         }
     }
 
@@ -39,6 +76,11 @@ impl Arena {
         &self.map
     }
 
+    /// Returns the terrain changes since the last call.
+    pub fn take_terrain_changes(&mut self) -> Vec<TerrainChange> {
+        self.map.take_changes()
+    }
+
     pub fn entities(&self) -> &HashMap<EntityId, Entity> {
         &self.entities
     }
@@ -47,6 +89,15 @@ impl Arena {
         &self.spells
     }
 
+    pub fn items(&self) -> &HashMap<ItemId, Item> {
+        &self.items
+    }
+
+    /// Statistics of the entity during the arena, even if it is dead.
+    pub fn entity_stats(&self, entity_id: EntityId) -> EntityStats {
+        self.stats.get(&entity_id).cloned().unwrap_or_default()
+    }
+
     pub fn create_entity(&mut self, character: Rc<Character>, position: Vec2) -> &mut Entity {
         let id = EntityId::next(self.last_entity_id);
         let entity = Entity::new(id, character, position);
@@ -63,11 +114,91 @@ impl Arena {
         self.spells.insert(id, spell);
     }
 
-    pub fn update(&mut self) {
+    /// Creates a random item in a free floor position, if the arena has room for more items.
+    fn spawn_item(&mut self) {
+        let floor = self.map.ground().iter().filter(|&&terrain| terrain == Terrain::Floor);
+        let max_items = (floor.count() / Self::FLOOR_PER_ITEM).max(1);
+        if self.items.len() >= max_items {
+            return
+        }
+
+        let mut rng = rand::thread_rng();
+        let free_position = self
+            .map
+            .ground()
+            .iter()
+            .enumerate()
+            .filter(|(_, &terrain)| terrain == Terrain::Floor)
+            .map(|(index, _)| self.map.position_of(index))
+            .filter(|&position| {
+                self.entities.values().all(|entity| entity.position() != position)
+                    && self.items.values().all(|item| item.position() != position)
+            })
+            .choose(&mut rng);
+
+        if let Some(position) = free_position {
+            let id = ItemId::next(self.last_item_id);
+            let kind = *ItemKind::ALL.choose(&mut rng).unwrap();
+            self.last_item_id = id;
+            self.items.insert(id, Item::new(id, kind, position));
+        }
+    }
+
+    /// Removes the entity from the arena as dead, with no killer.
+    pub fn eliminate_entity(&mut self, entity_id: EntityId) {
+        if let Some(mut entity) = self.entities.remove(&entity_id) {
+            entity.set_health(0);
+            entity.behaviour().destroyed();
+        }
+    }
+
+    /// Changes the terrain of the ring at `ring` positions from the border, except the walls.
+    pub fn turn_ring_into(&mut self, ring: usize, terrain: Terrain) {
+        let size = self.map.size();
+        for index in 0..size * size {
+            let position = self.map.position_of(index);
+            let (x, y) = (position.x as usize, position.y as usize);
+            let distance = x.min(y).min(size - 1 - x).min(size - 1 - y);
+            if distance == ring && self.map.terrain(position) != Terrain::Wall {
+                self.map.set_terrain(position, terrain);
+            }
+        }
+    }
+
+    /// Moves the entity to the paired pad if it is over a teleport pad and the pad is free.
+    fn teleport(&mut self, entity_id: EntityId) {
+        let position = self.entities[&entity_id].position();
+        if let Some(destination) = self.map.teleport_destination(position) {
+            let occupied = self.entities.values().any(|entity| entity.position() == destination);
+            if !occupied {
+                self.entities.get_mut(&entity_id).unwrap().set_position(destination);
+            }
+        }
+    }
+
+    /// The entity picks up the item placed in its position, if any.
+    fn pick_up_item(&mut self, entity_id: EntityId, current_time: Instant) {
+        let entity = self.entities.get_mut(&entity_id).unwrap();
+        let position = entity.position();
+        let item_id = self.items.values().find(|item| item.position() == position).map(Item::id);
+        if let Some(item_id) = item_id {
+            let item = self.items.remove(&item_id).unwrap();
+            item.apply(entity, current_time);
+        }
+    }
+
+    /// Updates the arena, returning the entities died during the update.
+    pub fn update(&mut self) -> Vec<Death> {
         assert!(self.spells.iter().all(|(_, spell)| !spell.is_destroyed()));
         assert!(self.entities.iter().all(|(_, entity)| entity.is_alive()));
 
         let current_time = Instant::now();
+        let mut deaths = Vec::new();
+
+        if current_time >= self.next_item_time {
+            self.spawn_item();
+            self.next_item_time = current_time + Self::ITEM_SPAWN_PERIOD;
+        }
 
         for spell in self.spells.values_mut() {
             let mut spell_actions = VecDeque::from(spell.behaviour().update(
@@ -81,19 +212,38 @@ impl Arena {
                 match action {
                     SpellAction::Move => {
                         spell.move_step(current_time);
-                        if self.map.terrain(spell.position()) != Terrain::Wall {
+                        if !self.map.terrain(spell.position()).is_wall() {
                             let entity_position = self
                                 .entities
                                 .values_mut()
                                 .find(|entity| entity.position() == spell.position());
 
                             if let Some(entity) = entity_position {
-                                if !spell.is_affected_entity(entity.id()) {
+                                let teammate = spell.team().is_some()
+                                    && spell.team() == entity.team()
+                                    && !self.friendly_fire;
+
+                                if !teammate && !spell.is_affected_entity(entity.id()) {
                                     let (actions, affect) =
                                         spell.behaviour().entity_collision(entity);
 
                                     if affect {
+                                        let previous_health = entity.health();
                                         entity.add_health(-spell.damage());
+                                        entity.set_last_attacker_id(spell.entity_origin_id());
+
+                                        let damage = previous_health - entity.health();
+                                        let stats = self.stats.entry(entity.id()).or_default();
+                                        stats.damage_taken += damage;
+
+                                        let origin_id = spell.entity_origin_id();
+                                        if entity.id() != origin_id {
+                                            let stats = self.stats.entry(origin_id).or_default();
+                                            stats.damage_dealt += damage;
+                                            if !spell.has_affected_entities() {
+                                                stats.spells_hit += 1;
+                                            }
+                                        }
                                         spell.add_affected_entity(entity.id());
                                     }
 
@@ -102,6 +252,7 @@ impl Arena {
                             }
                         }
                         else {
+                            self.map.damage_wall(spell.position(), spell.damage());
                             spell_actions.push_back(SpellAction::Destroy);
                         }
                     }
@@ -118,6 +269,20 @@ impl Arena {
             }
         }
 
+        if current_time >= self.next_lava_time {
+            for entity in self.entities.values_mut() {
+                if self.map.terrain(entity.position()) == Terrain::Lava {
+                    let previous_health = entity.health();
+                    entity.add_health(-Self::LAVA_DAMAGE);
+                    entity.clear_last_attacker_id();
+
+                    let stats = self.stats.entry(entity.id()).or_default();
+                    stats.damage_taken += previous_health - entity.health();
+                }
+            }
+            self.next_lava_time = current_time + Self::LAVA_PERIOD;
+        }
+
         for entity_id in self.entities.keys().copied().collect::<Vec<_>>() {
             let entity = &self.entities[&entity_id];
             let mut entity_actions = VecDeque::from(entity.behaviour().update(
@@ -137,7 +302,7 @@ impl Arena {
                         let entity = self.entities.get_mut(&entity_id).unwrap();
                         entity.set_direction(direction);
                         let next_position = entity.position() + direction.to_vec2();
-                        if self.map.terrain(next_position) != Terrain::Wall {
+                        if !self.map.terrain(next_position).is_wall() {
                             let occupied_position = self
                                 .entities
                                 .values()
@@ -145,7 +310,11 @@ impl Arena {
 
                             if !occupied_position {
                                 let entity = self.entities.get_mut(&entity_id).unwrap();
-                                entity.walk(current_time);
+                                let speed_factor = self.map.speed_factor(entity.position());
+                                if entity.walk(current_time, speed_factor) {
+                                    self.teleport(entity_id);
+                                    self.pick_up_item(entity_id, current_time);
+                                }
                             }
                         }
                     }
@@ -153,11 +322,13 @@ impl Arena {
                         let entity = self.entities.get_mut(&entity_id).unwrap();
                         entity.set_direction(direction);
                         self.create_spell(SpellSpecId(1), entity_id);
+                        self.stats.entry(entity_id).or_default().spells_cast += 1;
                     }
                     EntityAction::Destroy => {
                         let entity = self.entities.get_mut(&entity_id).unwrap();
                         entity.set_health(0);
                         entity.behaviour().destroyed();
+                        deaths.push(Death { entity_id, killer_id: entity.last_attacker_id() });
                     }
                 }
             }
@@ -165,5 +336,6 @@ impl Arena {
 
         self.spells.retain(|_, spell| !spell.is_destroyed());
         self.entities.retain(|_, entity| entity.is_alive());
+        deaths
     }
 }
@@ -31,12 +31,16 @@ pub trait EntityBehaviour {
 pub struct Entity {
     id: EntityId,
     character: Rc<Character>,
+    team: Option<usize>,
+    last_attacker_id: Option<EntityId>,
     behaviour: RefCell<Box<dyn EntityBehaviour>>,
     direction: Direction,
     position: Vec2,
     health: usize,
     energy: usize,
     speed: f32,
+    speed_boost: Option<(f32, Instant)>, // Factor, end time
+    spell_damage: i32,                   // Extra damage of the spells cast by the entity
     next_walk_time: Instant,
 }
 
@@ -45,11 +49,15 @@ impl Entity {
         Entity {
             id,
             position,
+            team: None,
+            last_attacker_id: None,
             behaviour: RefCell::new(get_behaviour(character.id().behaviour_name())),
             direction: Direction::Down,
             health: character.max_health(),
             energy: character.max_energy(),
             speed: character.speed_base(),
+            speed_boost: None,
+            spell_damage: 0,
             next_walk_time: Instant::now(),
             character,
         }
@@ -60,7 +68,29 @@ impl Entity {
     }
 
     pub fn character(&self) -> &Character {
-        &*self.character
+        &self.character
+    }
+
+    pub fn team(&self) -> Option<usize> {
+        self.team
+    }
+
+    pub fn set_team(&mut self, team: Option<usize>) {
+        self.team = team;
+    }
+
+    /// Last entity that has damaged this entity.
+    pub fn last_attacker_id(&self) -> Option<EntityId> {
+        self.last_attacker_id
+    }
+
+    pub fn set_last_attacker_id(&mut self, entity_id: EntityId) {
+        self.last_attacker_id = Some(entity_id);
+    }
+
+    /// The last damage does not come from an entity (e.g. the terrain).
+    pub fn clear_last_attacker_id(&mut self) {
+        self.last_attacker_id = None;
     }
 
     pub fn behaviour(&self) -> RefMut<'_, Box<dyn EntityBehaviour>> {
@@ -99,6 +129,7 @@ impl Entity {
         self.position = position;
     }
 
+    #[allow(dead_code)]
     pub fn displace(&mut self, displacement: Vec2) {
         self.position += displacement;
     }
@@ -112,6 +143,7 @@ impl Entity {
         }
     }
 
+    #[allow(dead_code)]
     pub fn set_energy(&mut self, energy: usize) {
         if energy > self.character().max_energy() {
             self.energy = self.character().max_energy();
@@ -147,10 +179,32 @@ impl Entity {
         }
     }
 
-    pub fn walk(&mut self, current: Instant) -> bool {
+    pub fn spell_damage(&self) -> i32 {
+        self.spell_damage
+    }
+
+    pub fn add_spell_damage(&mut self, damage: i32) {
+        self.spell_damage += damage;
+    }
+
+    /// Multiplies the speed by the factor until the end time.
+    pub fn set_speed_boost(&mut self, factor: f32, until: Instant) {
+        self.speed_boost = Some((factor, until));
+    }
+
+    fn current_speed(&self, current: Instant) -> f32 {
+        match self.speed_boost {
+            Some((factor, until)) if current < until => self.speed * factor,
+            _ => self.speed,
+        }
+    }
+
+    /// Walks a step if the entity is ready for it. The speed factor depends on the terrain.
+    pub fn walk(&mut self, current: Instant, speed_factor: f32) -> bool {
         if current > self.next_walk_time {
             self.position += self.direction.to_vec2();
-            self.next_walk_time = current + Duration::from_secs_f32(1.0 / self.speed);
+            let speed = self.current_speed(current) * speed_factor;
+            self.next_walk_time = current + Duration::from_secs_f32(1.0 / speed);
             return true
         }
         false
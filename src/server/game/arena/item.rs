@@ -0,0 +1,50 @@
+use super::entity::{Entity};
+
+use crate::vec2::{Vec2};
+use crate::ids::{ItemId};
+use crate::message::{ItemKind};
+
+use std::time::{Instant, Duration};
+
+pub struct Item {
+    id: ItemId,
+    kind: ItemKind,
+    position: Vec2,
+}
+
+impl Item {
+    pub const HEALTH_POTION_HEALTH: i32 = 30;
+    pub const ENERGY_CRYSTAL_ENERGY: i32 = 50;
+    pub const SPEED_BOOTS_FACTOR: f32 = 1.5;
+    pub const SPEED_BOOTS_DURATION: Duration = Duration::from_secs(8);
+    pub const SPELL_UPGRADE_DAMAGE: i32 = 2;
+
+    pub fn new(id: ItemId, kind: ItemKind, position: Vec2) -> Item {
+        Item { id, kind, position }
+    }
+
+    pub fn id(&self) -> ItemId {
+        self.id
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        self.kind
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    /// Applies the effect of the item to the entity that picks it up.
+    pub fn apply(&self, entity: &mut Entity, current_time: Instant) {
+        match self.kind {
+            ItemKind::HealthPotion => entity.add_health(Self::HEALTH_POTION_HEALTH),
+            ItemKind::EnergyCrystal => entity.add_energy(Self::ENERGY_CRYSTAL_ENERGY),
+            ItemKind::SpeedBoots => entity.set_speed_boost(
+                Self::SPEED_BOOTS_FACTOR,
+                current_time + Self::SPEED_BOOTS_DURATION,
+            ),
+            ItemKind::SpellUpgrade => entity.add_spell_damage(Self::SPELL_UPGRADE_DAMAGE),
+        }
+    }
+}
@@ -1,17 +1,43 @@
 use crate::vec2::Vec2;
-use crate::message::Terrain;
+use crate::message::{Terrain, TerrainChange};
+
+use rand::{Rng};
+use rand::rngs::{ThreadRng};
+
+use std::collections::{HashMap};
 
 pub struct Map {
     size: usize,
     ground: Vec<Terrain>,
+    wall_health: HashMap<usize, i32>, // Remaining health of the breakable walls
+    teleports: HashMap<usize, usize>, // Destination of each teleport pad
+    changes: Vec<TerrainChange>,
 }
 
 impl Map {
-    pub fn new(size: usize) -> Map {
-        Map { size, ground: Self::build_ground(size, 0) }
+    pub const BREAKABLE_WALL_HEALTH: i32 = 20;
+
+    /// Number of inner positions of the map for each terrain patch.
+    const POSITIONS_PER_PATCH: usize = 40;
+    const MAX_PATCH_SIZE: usize = 6;
+
+    /// Minimum map size to place a pair of teleport pads.
+    const MIN_TELEPORT_MAP_SIZE: usize = 12;
+
+    /// The features never cover the `spawn_tiles` floor positions needed to place the players.
+    pub fn new(size: usize, spawn_tiles: usize) -> Map {
+        let mut map = Map {
+            size,
+            ground: Self::build_ground(size),
+            wall_health: HashMap::new(),
+            teleports: HashMap::new(),
+            changes: Vec::new(),
+        };
+        map.build_features(spawn_tiles, &mut rand::thread_rng());
+        map
     }
 
-    fn build_ground(size: usize, _seed: usize) -> Vec<Terrain> {
+    fn build_ground(size: usize) -> Vec<Terrain> {
         (0..size * size)
             .map(|index| {
                 let x = index % size;
@@ -27,18 +53,138 @@ impl Map {
             .collect()
     }
 
+    /// Places random patches of special terrain and a pair of teleport pads.
+    fn build_features(&mut self, spawn_tiles: usize, rng: &mut ThreadRng) {
+        const PATCH_TERRAINS: [Terrain; 5] = [
+            Terrain::BreakableWall,
+            Terrain::BreakableWall,
+            Terrain::Water,
+            Terrain::Mud,
+            Terrain::Lava,
+        ];
+
+        let inner_size = self.size.saturating_sub(2);
+        let mut free_floor = (inner_size * inner_size).saturating_sub(spawn_tiles);
+        let patches = inner_size * inner_size / Self::POSITIONS_PER_PATCH;
+        for _ in 0..patches {
+            let terrain = PATCH_TERRAINS[rng.gen_range(0..PATCH_TERRAINS.len())];
+            let mut position = self.random_inner_position(rng);
+            for _ in 0..rng.gen_range(1..=Self::MAX_PATCH_SIZE) {
+                let index = self.index(position);
+                if self.ground[index] == Terrain::Floor && free_floor > 0 {
+                    free_floor -= 1;
+                    self.ground[index] = terrain;
+                    if terrain == Terrain::BreakableWall {
+                        self.wall_health.insert(index, Self::BREAKABLE_WALL_HEALTH);
+                    }
+                }
+
+                let step = match rng.gen_range(0..4) {
+                    0 => Vec2::x(1),
+                    1 => Vec2::x(-1),
+                    2 => Vec2::y(1),
+                    _ => Vec2::y(-1),
+                };
+                if self.is_inner(position + step) {
+                    position += step;
+                }
+            }
+        }
+
+        if self.size >= Self::MIN_TELEPORT_MAP_SIZE && free_floor >= 2 {
+            let first = self.index(self.random_inner_position(rng));
+            let second = self.index(self.random_inner_position(rng));
+            if first != second {
+                for &index in &[first, second] {
+                    self.ground[index] = Terrain::Teleport;
+                    self.wall_health.remove(&index);
+                }
+                self.teleports.insert(first, second);
+                self.teleports.insert(second, first);
+            }
+        }
+    }
+
+    fn random_inner_position(&self, rng: &mut ThreadRng) -> Vec2 {
+        let limit = self.size as i32 - 1;
+        Vec2::xy(rng.gen_range(1..limit), rng.gen_range(1..limit))
+    }
+
+    fn is_inner(&self, position: Vec2) -> bool {
+        let limit = self.size as i32 - 1;
+        position.x > 0 && position.y > 0 && position.x < limit && position.y < limit
+    }
+
+    fn index(&self, position: Vec2) -> usize {
+        assert!(position.x >= 0 && position.x < self.size as i32);
+        assert!(position.y >= 0 && position.y < self.size as i32);
+        position.y as usize * self.size + position.x as usize
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     pub fn ground(&self) -> &Vec<Terrain> {
         &self.ground
     }
 
     pub fn terrain(&self, position: Vec2) -> Terrain {
-        assert!(position.x >= 0 && position.x < self.size as i32);
-        assert!(position.y >= 0 && position.y < self.size as i32);
-        self.ground[position.y as usize * self.size + position.x as usize]
+        self.ground[self.index(position)]
     }
 
     pub fn position_of(&self, index: usize) -> Vec2 {
         assert!(index < self.size * self.size);
         Vec2::xy((index % self.size) as i32, (index / self.size) as i32)
     }
+
+    /// Factor applied to the speed of the entities that walk from this position.
+    pub fn speed_factor(&self, position: Vec2) -> f32 {
+        match self.terrain(position) {
+            Terrain::Water => 0.6,
+            Terrain::Mud => 0.4,
+            _ => 1.0,
+        }
+    }
+
+    /// Position where a teleport pad sends the entities, if the position is a teleport pad.
+    pub fn teleport_destination(&self, position: Vec2) -> Option<Vec2> {
+        let index = self.index(position);
+        match self.ground[index] {
+            Terrain::Teleport => self
+                .teleports
+                .get(&index)
+                .filter(|&&destination| self.ground[destination] == Terrain::Teleport)
+                .map(|&destination| self.position_of(destination)),
+            _ => None,
+        }
+    }
+
+    pub fn set_terrain(&mut self, position: Vec2, terrain: Terrain) {
+        let index = self.index(position);
+        if self.ground[index] != terrain {
+            if terrain != Terrain::BreakableWall {
+                self.wall_health.remove(&index);
+            }
+            self.ground[index] = terrain;
+            self.changes.push(TerrainChange { position, terrain });
+        }
+    }
+
+    /// Damages a breakable wall. When its health runs out, it becomes floor.
+    pub fn damage_wall(&mut self, position: Vec2, damage: i32) {
+        let index = self.index(position);
+        if let Some(health) = self.wall_health.get_mut(&index) {
+            *health -= damage;
+            if *health <= 0 {
+                self.wall_health.remove(&index);
+                self.set_terrain(position, Terrain::Floor);
+            }
+        }
+    }
+
+    /// Returns the terrain changes since the last call.
+    pub fn take_changes(&mut self) -> Vec<TerrainChange> {
+        std::mem::take(&mut self.changes)
+    }
 }
@@ -22,6 +22,7 @@ pub trait SpellBehaviour: Send + Sync {
     ) -> Vec<SpellAction>;
 }
 
+#[allow(dead_code)]
 pub enum SpellAction {
     SetSpeed(f32),
     SetDirection(Direction),
@@ -35,6 +36,7 @@ pub struct Spell {
     id: SpellId,
     spec_id: SpellSpecId,
     entity_origin_id: EntityId,
+    team: Option<usize>,
     behaviour: RefCell<Box<dyn SpellBehaviour>>,
     damage: i32,
     //effects
@@ -53,8 +55,9 @@ impl Spell {
             id,
             spec_id,
             entity_origin_id: entity.id(),
+            team: entity.team(),
             behaviour: RefCell::new(get_behaviour(spec.behaviour_name)),
-            damage: spec.damage, /* Mul to entity effects */
+            damage: spec.damage + entity.spell_damage(),
             position: entity.position() + entity.direction().to_vec2(),
             direction: entity.direction(),
             speed: spec.speed,
@@ -76,6 +79,10 @@ impl Spell {
         self.entity_origin_id
     }
 
+    pub fn team(&self) -> Option<usize> {
+        self.team
+    }
+
     pub fn damage(&self) -> i32 {
         self.damage
     }
@@ -92,18 +99,22 @@ impl Spell {
         self.position
     }
 
+    #[allow(dead_code)]
     pub fn direction(&self) -> Direction {
         self.direction
     }
 
+    #[allow(dead_code)]
     pub fn speed(&self) -> f32 {
         self.speed
     }
 
+    #[allow(dead_code)]
     pub fn set_position(&mut self, position: Vec2) {
         self.position = position;
     }
 
+    #[allow(dead_code)]
     pub fn displace(&mut self, displacement: Vec2) {
         self.position += displacement;
     }
@@ -129,6 +140,10 @@ impl Spell {
         self.affected_entities.insert(entity_id);
     }
 
+    pub fn has_affected_entities(&self) -> bool {
+        !self.affected_entities.is_empty()
+    }
+
     pub fn is_affected_entity(&self, entity_id: EntityId) -> bool {
         self.affected_entities.contains(&entity_id)
     }
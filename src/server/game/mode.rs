@@ -0,0 +1,137 @@
+use super::{Game};
+
+use crate::message::{GameModeKind};
+
+use std::time::{Duration};
+
+/// Death of a player during a game step.
+/// The killer is the victim itself for suicides and none for environment deaths.
+#[derive(Debug, Clone)]
+pub struct PlayerDeath {
+    pub victim: char,
+    pub killer: Option<char>,
+}
+
+/// Points to add to the players (by character symbol) and to the teams.
+#[derive(Default)]
+pub struct Score {
+    pub players: Vec<(char, usize)>,
+    pub teams: Vec<(usize, usize)>,
+}
+
+/// Rules of the game: how the points are earned and when the arenas and the game finish.
+pub trait GameMode {
+    fn kind(&self) -> GameModeKind;
+
+    fn score(&self, game: &Game, deaths: &[PlayerDeath]) -> Score;
+
+    /// Maximum duration of the game, if any.
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn has_arena_finished(&self, game: &Game) -> bool {
+        match game.has_teams() {
+            true => game.living_teams().len() <= 1,
+            false => game.living_players().len() <= 1,
+        }
+    }
+
+    fn has_finished(&self, game: &Game) -> bool {
+        match game.has_teams() {
+            true => game.team_points().iter().any(|&points| points >= game.winner_points()),
+            false => game.players().values().any(|player| player.points() >= game.winner_points()),
+        }
+    }
+}
+
+pub fn create(kind: GameModeKind, duration: Duration) -> Box<dyn GameMode> {
+    match kind {
+        GameModeKind::LastManStanding => Box::new(LastManStanding),
+        GameModeKind::KillCount => Box::new(KillCount),
+        GameModeKind::Timed => Box::new(Timed { duration }),
+    }
+}
+
+/// The survivors earn a point for each player that dies.
+/// With teams, each surviving team earns a point for each rival that dies.
+pub struct LastManStanding;
+
+impl GameMode for LastManStanding {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::LastManStanding
+    }
+
+    fn score(&self, game: &Game, deaths: &[PlayerDeath]) -> Score {
+        let mut score = Score::default();
+        if deaths.is_empty() {
+            return score
+        }
+
+        for symbol in game.living_players() {
+            score.players.push((symbol, deaths.len()));
+        }
+
+        for death in deaths {
+            let dead_team = game.players()[&death.victim].team();
+            for team in game.living_teams() {
+                if Some(team) != dead_team {
+                    score.teams.push((team, 1));
+                }
+            }
+        }
+        score
+    }
+}
+
+/// The killer earns a point for each rival killed.
+pub struct KillCount;
+
+impl GameMode for KillCount {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::KillCount
+    }
+
+    fn score(&self, game: &Game, deaths: &[PlayerDeath]) -> Score {
+        let mut score = Score::default();
+        for death in deaths {
+            if let Some(killer) = death.killer {
+                let killer_team = game.players()[&killer].team();
+                let victim_team = game.players()[&death.victim].team();
+                let teammate = killer_team.is_some() && killer_team == victim_team;
+                let rival = killer != death.victim && !teammate;
+
+                if rival {
+                    score.players.push((killer, 1));
+                    if let Some(team) = killer_team {
+                        score.teams.push((team, 1));
+                    }
+                }
+            }
+        }
+        score
+    }
+}
+
+/// Kill count scoring during a fixed time. The highest score wins when the time is over.
+pub struct Timed {
+    duration: Duration,
+}
+
+impl GameMode for Timed {
+    fn kind(&self) -> GameModeKind {
+        GameModeKind::Timed
+    }
+
+    fn score(&self, game: &Game, deaths: &[PlayerDeath]) -> Score {
+        KillCount.score(game, deaths)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Some(self.duration)
+    }
+
+    fn has_finished(&self, game: &Game) -> bool {
+        game.elapsed_time() >= self.duration
+    }
+}
@@ -2,6 +2,7 @@ use super::arena::entity::{EntityAction, EntityBehaviour, Entity};
 use super::arena::map::{Map};
 
 use crate::character::{Character};
+use crate::message::{TerrainChange};
 use crate::direction::{Direction};
 use crate::ids::{SkillId, EntityId};
 
@@ -11,9 +12,12 @@ use std::cell::{RefCell};
 use std::collections::{HashMap};
 
 pub struct Player {
+    name: String,
     character: Rc<Character>,
+    team: Option<usize>,
     entity_handler: Rc<RefCell<EntityHandler>>,
     points: usize,
+    hidden_terrain_changes: Vec<TerrainChange>, // Out of the sight of the player until now
 }
 
 impl Player {
@@ -21,18 +25,32 @@ impl Player {
     pub const MAX_ENERGY: usize = 100;
     pub const SPEED_BASE: f32 = 8.0;
 
-    pub fn new(character: Rc<Character>) -> Player {
+    /// Maximum number of actions waiting to be processed in the next step.
+    pub const MAX_PENDING_ACTIONS: usize = 8;
+
+    pub fn new(name: String, character: Rc<Character>, team: Option<usize>) -> Player {
         Player {
+            name,
             character,
+            team,
             entity_handler: Rc::new(RefCell::new(EntityHandler::default())),
             points: 0,
+            hidden_terrain_changes: Vec::new(),
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn character(&self) -> &Rc<Character> {
         &self.character
     }
 
+    pub fn team(&self) -> Option<usize> {
+        self.team
+    }
+
     pub fn entity_id(&self) -> EntityId {
         self.entity_handler.borrow().entity_id
     }
@@ -45,18 +63,46 @@ impl Player {
         self.entity_handler.borrow().entity_id != EntityId::NONE
     }
 
-    pub fn walk(&mut self, direction: Direction) {
-        self.entity_handler.borrow_mut().actions.push(EntityAction::Walk(direction))
+    /// Only the basic skill exists for now.
+    pub fn has_skill(&self, id: SkillId) -> bool {
+        id == SkillId(1)
     }
 
-    pub fn cast(&mut self, direction: Direction, id: SkillId) {
-        self.entity_handler.borrow_mut().actions.push(EntityAction::Cast(direction, id))
+    /// Returns `false` if there are too many pending actions. In this case, it is discarded.
+    pub fn walk(&mut self, direction: Direction) -> bool {
+        self.entity_handler.borrow_mut().push_action(EntityAction::Walk(direction))
+    }
+
+    /// Returns `false` if there are too many pending actions. In this case, it is discarded.
+    pub fn cast(&mut self, direction: Direction, id: SkillId) -> bool {
+        self.entity_handler.borrow_mut().push_action(EntityAction::Cast(direction, id))
     }
 
     pub fn add_points(&mut self, points: usize) {
         self.points += points;
     }
 
+    /// Registers a terrain change that the player has not seen yet.
+    /// Only the last change of each position is kept.
+    pub fn hide_terrain_change(&mut self, change: TerrainChange) {
+        self.hidden_terrain_changes.retain(|hidden| hidden.position != change.position);
+        self.hidden_terrain_changes.push(change);
+    }
+
+    /// Removes the hidden terrain changes that are visible now, returning them.
+    pub fn reveal_terrain_changes(
+        &mut self,
+        is_visible: impl Fn(&TerrainChange) -> bool,
+    ) -> Vec<TerrainChange> {
+        let (visible, hidden) = self.hidden_terrain_changes.drain(..).partition(is_visible);
+        self.hidden_terrain_changes = hidden;
+        visible
+    }
+
+    pub fn clear_terrain_changes(&mut self) {
+        self.hidden_terrain_changes.clear();
+    }
+
     pub fn create_entity_behaviour(&mut self, entity_id: EntityId) -> Box<PlayerBehaviour> {
         self.entity_handler.borrow_mut().entity_id = entity_id;
         Box::new(PlayerBehaviour { entity_handler: self.entity_handler.clone() })
@@ -69,6 +115,16 @@ pub struct EntityHandler {
     actions: Vec<EntityAction>,
 }
 
+impl EntityHandler {
+    fn push_action(&mut self, action: EntityAction) -> bool {
+        if self.actions.len() < Player::MAX_PENDING_ACTIONS {
+            self.actions.push(action);
+            return true
+        }
+        false
+    }
+}
+
 pub struct PlayerBehaviour {
     entity_handler: Rc<RefCell<EntityHandler>>,
 }
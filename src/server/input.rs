@@ -0,0 +1,104 @@
+use crate::ids::{SessionToken};
+
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+
+/// Input of a player that breaks the limits.
+#[derive(Debug, Clone, Copy)]
+pub enum Violation {
+    MoveRate,
+    CastRate,
+    QueueFull,
+    UnknownSkill,
+}
+
+impl Violation {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Violation::MoveRate => "moves faster than the character speed",
+            Violation::CastRate => "casts faster than allowed",
+            Violation::QueueFull => "too many pending actions",
+            Violation::UnknownSkill => "unknown skill",
+        }
+    }
+}
+
+#[derive(Default)]
+struct InputRecord {
+    moves: VecDeque<Instant>,
+    casts: VecDeque<Instant>,
+    strikes: usize,
+    last_strike: Option<Instant>,
+}
+
+/// Limits the rate of the player input of each session.
+/// Each rejected input is a strike, and the sessions with too many strikes must be kicked.
+pub struct InputLimiter {
+    move_tolerance: f32,
+    max_casts: usize,
+    max_strikes: usize,
+    records: HashMap<SessionToken, InputRecord>,
+}
+
+impl InputLimiter {
+    const PERIOD: Duration = Duration::from_secs(1);
+
+    /// Time without violations to forget the strikes of a session.
+    const STRIKE_EXPIRATION: Duration = Duration::from_secs(10);
+
+    /// The `move_tolerance` multiplies the character speed to obtain the moves allowed
+    /// by second, given margin to the direction changes and the speed boosts.
+    pub fn new(move_tolerance: f32, max_casts: usize, max_strikes: usize) -> InputLimiter {
+        InputLimiter { move_tolerance, max_casts, max_strikes, records: HashMap::new() }
+    }
+
+    fn allow(history: &mut VecDeque<Instant>, max_inputs: usize) -> bool {
+        let now = Instant::now();
+        while let Some(&time) = history.front() {
+            if now.duration_since(time) < Self::PERIOD {
+                break
+            }
+            history.pop_front();
+        }
+
+        if history.len() < max_inputs {
+            history.push_back(now);
+            return true
+        }
+        false
+    }
+
+    /// Registers a move of a character with the speed.
+    /// Returns `false` if the session has exceeded the limit.
+    pub fn allow_move(&mut self, token: SessionToken, speed: f32) -> bool {
+        let max_moves = (speed * self.move_tolerance).ceil() as usize;
+        Self::allow(&mut self.records.entry(token).or_default().moves, max_moves)
+    }
+
+    /// Registers a cast. Returns `false` if the session has exceeded the limit.
+    pub fn allow_cast(&mut self, token: SessionToken) -> bool {
+        Self::allow(&mut self.records.entry(token).or_default().casts, self.max_casts)
+    }
+
+    /// Adds a strike to the session. Returns the current strikes and if they reach the limit.
+    pub fn strike(&mut self, token: SessionToken) -> (usize, bool) {
+        let now = Instant::now();
+        let record = self.records.entry(token).or_default();
+        if let Some(last_strike) = record.last_strike {
+            if now.duration_since(last_strike) > Self::STRIKE_EXPIRATION {
+                record.strikes = 0;
+            }
+        }
+        record.strikes += 1;
+        record.last_strike = Some(now);
+        (record.strikes, record.strikes >= self.max_strikes)
+    }
+
+    pub fn max_strikes(&self) -> usize {
+        self.max_strikes
+    }
+
+    pub fn remove(&mut self, token: SessionToken) {
+        self.records.remove(&token);
+    }
+}
@@ -0,0 +1,148 @@
+use message_io::network::{Endpoint, Transport};
+
+use std::time::{Duration};
+use std::fmt::{Write, Display};
+
+/// Counts of observations in buckets of upper bounds, as the prometheus histograms.
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>, // Non cumulative, by bound
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram { bounds, counts: vec![0; bounds.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(index) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.counts[index] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct TransportCounter {
+    messages: u64,
+    bytes: u64,
+}
+
+/// Values read from the server state at the moment of the request.
+pub struct Gauges {
+    pub connections: usize,
+    pub sessions: usize, // Connected sessions
+    pub subscriptions: usize,
+    pub rooms: usize,
+    pub games_running: usize,
+}
+
+/// Server statistics, exposed in the prometheus text format.
+pub struct Metrics {
+    games_played: u64,
+    game_step_duration: Histogram,
+    sent_tcp: TransportCounter,
+    sent_udp: TransportCounter,
+    decode_failures: u64,
+    udp_handshake_failures: u64,
+}
+
+impl Metrics {
+    const GAME_STEP_BUCKETS: [f64; 9] =
+        [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05];
+
+    pub fn new() -> Metrics {
+        Metrics {
+            games_played: 0,
+            game_step_duration: Histogram::new(&Self::GAME_STEP_BUCKETS),
+            sent_tcp: TransportCounter::default(),
+            sent_udp: TransportCounter::default(),
+            decode_failures: 0,
+            udp_handshake_failures: 0,
+        }
+    }
+
+    pub fn game_played(&mut self) {
+        self.games_played += 1;
+    }
+
+    pub fn game_step_processed(&mut self, duration: Duration) {
+        self.game_step_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn data_sent(&mut self, endpoint: Endpoint, bytes: usize) {
+        let counter = match endpoint.resource_id().adapter_id() == Transport::Udp.id() {
+            true => &mut self.sent_udp,
+            false => &mut self.sent_tcp,
+        };
+        counter.messages += 1;
+        counter.bytes += bytes as u64;
+    }
+
+    pub fn decode_failed(&mut self) {
+        self.decode_failures += 1;
+    }
+
+    pub fn udp_handshake_failed(&mut self) {
+        self.udp_handshake_failures += 1;
+    }
+
+    fn write_header(output: &mut String, name: &str, kind: &str, help: &str) {
+        writeln!(output, "# HELP asciiarena_{} {}", name, help).unwrap();
+        writeln!(output, "# TYPE asciiarena_{} {}", name, kind).unwrap();
+    }
+
+    fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+        Self::write_header(output, name, kind, help);
+        writeln!(output, "asciiarena_{} {}", name, value).unwrap();
+    }
+
+    fn write_transport_metric(output: &mut String, name: &str, help: &str, values: [u64; 2]) {
+        Self::write_header(output, name, "counter", help);
+        writeln!(output, "asciiarena_{}{{transport=\"tcp\"}} {}", name, values[0]).unwrap();
+        writeln!(output, "asciiarena_{}{{transport=\"udp\"}} {}", name, values[1]).unwrap();
+    }
+
+    fn write_histogram(output: &mut String, name: &str, help: &str, histogram: &Histogram) {
+        Self::write_header(output, name, "histogram", help);
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            writeln!(output, "asciiarena_{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative)
+                .unwrap();
+        }
+        writeln!(output, "asciiarena_{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count).unwrap();
+        writeln!(output, "asciiarena_{}_sum {}", name, histogram.sum).unwrap();
+        writeln!(output, "asciiarena_{}_count {}", name, histogram.count).unwrap();
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut output = String::new();
+        let out = &mut output;
+        Self::write_metric(out, "connections", "gauge", "Open tcp connections", gauges.connections);
+        Self::write_metric(out, "sessions", "gauge", "Connected players", gauges.sessions);
+        Self::write_metric(out, "subscriptions", "gauge", "Subscriptions", gauges.subscriptions);
+        Self::write_metric(out, "rooms", "gauge", "Existing rooms", gauges.rooms);
+        Self::write_metric(out, "games_running", "gauge", "Running games", gauges.games_running);
+        Self::write_metric(out, "games_played_total", "counter", "Games played", self.games_played);
+
+        let help = "Processing time of a game step";
+        Self::write_histogram(out, "game_step_seconds", help, &self.game_step_duration);
+
+        let messages = [self.sent_tcp.messages, self.sent_udp.messages];
+        Self::write_transport_metric(out, "messages_sent_total", "Messages sent", messages);
+        let bytes = [self.sent_tcp.bytes, self.sent_udp.bytes];
+        Self::write_transport_metric(out, "bytes_sent_total", "Bytes sent", bytes);
+
+        let help = "Received messages that can not be decoded";
+        Self::write_metric(out, "decode_failures_total", "counter", help, self.decode_failures);
+        let help = "Rejected udp handshakes";
+        let failures = self.udp_handshake_failures;
+        Self::write_metric(out, "udp_handshake_failures_total", "counter", help, failures);
+
+        output
+    }
+}
@@ -0,0 +1,163 @@
+use crate::message::{RatingEntry};
+
+use serde::{Serialize, Deserialize};
+
+use std::collections::{BTreeMap};
+use std::path::{PathBuf};
+use std::fs::{self};
+use std::io::{ErrorKind};
+use std::cmp::{Ordering};
+
+/// Result of a player in a finished game.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MatchResult {
+    pub name: String,
+    pub team: Option<usize>,
+    pub score: usize, // Team points with teams, player points without them
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PlayerRecord {
+    rating: f64,
+    games: usize,
+    wins: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MatchRecord {
+    date: String,
+    room: String,
+    results: Vec<MatchResult>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Database {
+    players: BTreeMap<String, PlayerRecord>,
+    matches: Vec<MatchRecord>,
+}
+
+/// Elo ratings of the players by name, persisted with the match results in a json file.
+pub struct RatingDatabase {
+    path: Option<PathBuf>, // Only in memory if none
+    database: Database,
+}
+
+impl RatingDatabase {
+    pub const INITIAL_RATING: f64 = 1500.0;
+
+    /// Maximum rating change of a player in a game.
+    const K_FACTOR: f64 = 32.0;
+
+    /// Loads the database from the file, or creates an empty one if the file does not exist.
+    pub fn load(path: Option<PathBuf>) -> Option<RatingDatabase> {
+        let database = match &path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(content) => match serde_json::from_str(&content) {
+                    Ok(database) => database,
+                    Err(err) => {
+                        log::error!("Can not parse ratings file '{}': {}", path.display(), err);
+                        return None
+                    }
+                },
+                Err(err) if err.kind() == ErrorKind::NotFound => Database::default(),
+                Err(err) => {
+                    log::error!("Can not read ratings file '{}': {}", path.display(), err);
+                    return None
+                }
+            },
+            None => Database::default(),
+        };
+
+        Some(RatingDatabase { path, database })
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let content = serde_json::to_string_pretty(&self.database).unwrap();
+            // Written in other file first to not corrupt the database if the server stops.
+            let temporal_path = path.with_extension("tmp");
+            let result = fs::write(&temporal_path, content)
+                .and_then(|_| fs::rename(&temporal_path, path));
+
+            if let Err(err) = result {
+                log::error!("Can not save ratings file '{}': {}", path.display(), err);
+            }
+        }
+    }
+
+    fn exact_rating(&self, name: &str) -> f64 {
+        let rating = self.database.players.get(name).map(|player| player.rating);
+        rating.unwrap_or(Self::INITIAL_RATING)
+    }
+
+    pub fn rating(&self, name: &str) -> u32 {
+        self.exact_rating(name).round() as u32
+    }
+
+    /// Players sorted by rating.
+    pub fn leaderboard(&self, max_entries: usize) -> Vec<RatingEntry> {
+        let mut players = self.database.players.iter().collect::<Vec<_>>();
+        players.sort_by(|(_, a), (_, b)| b.rating.partial_cmp(&a.rating).unwrap());
+        players
+            .into_iter()
+            .take(max_entries)
+            .map(|(name, player)| RatingEntry {
+                name: name.clone(),
+                rating: player.rating.round() as u32,
+                games: player.games,
+                wins: player.wins,
+            })
+            .collect()
+    }
+
+    /// Updates the ratings comparing the score of each player against each opponent.
+    /// Teammates are not compared between them.
+    pub fn record_match(&mut self, room: &str, results: Vec<MatchResult>) {
+        let ratings =
+            results.iter().map(|result| self.exact_rating(&result.name)).collect::<Vec<_>>();
+        let best_score = results.iter().map(|result| result.score).max().unwrap_or(0);
+
+        for (index, result) in results.iter().enumerate() {
+            let mut expected = 0.0;
+            let mut actual = 0.0;
+            let mut opponents = 0;
+            for (other_index, other) in results.iter().enumerate() {
+                let teammates = result.team.is_some() && result.team == other.team;
+                if index == other_index || teammates {
+                    continue
+                }
+
+                let rating_diff = ratings[other_index] - ratings[index];
+                expected += 1.0 / (1.0 + 10f64.powf(rating_diff / 400.0));
+                actual += match result.score.cmp(&other.score) {
+                    Ordering::Greater => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Less => 0.0,
+                };
+                opponents += 1;
+            }
+
+            let player = self.database.players.entry(result.name.clone()).or_insert(PlayerRecord {
+                rating: Self::INITIAL_RATING,
+                games: 0,
+                wins: 0,
+            });
+
+            if opponents > 0 {
+                player.rating += Self::K_FACTOR * (actual - expected) / opponents as f64;
+            }
+            player.games += 1;
+            if result.score == best_score {
+                player.wins += 1;
+            }
+        }
+
+        self.database.matches.push(MatchRecord {
+            date: chrono::Local::now().to_rfc3339(),
+            room: room.into(),
+            results,
+        });
+
+        self.save();
+    }
+}
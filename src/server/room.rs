@@ -0,0 +1,236 @@
+use super::session::{RoomSession, Session};
+use super::ratings::{RatingDatabase};
+use super::game::{Game, GameConfig};
+
+use crate::message::{RoomSettings, RoomSummary, LoggedPlayer, GameModeKind, CatchUp};
+use crate::util::{self};
+
+use message_io::network::{Endpoint};
+
+use std::time::{Instant};
+use std::collections::{HashSet, HashMap};
+
+#[derive(Clone)]
+pub struct RoomConfig {
+    pub min_players: u8, // Ready players to start a game
+    pub max_players: u8,
+    pub late_join: Option<CatchUp>, // Rule to join a running game, none if not allowed
+    pub game: GameConfig,
+}
+
+impl RoomConfig {
+    pub const MAX_NAME_LEN: usize = 16;
+    pub const MIN_MAP_SIZE: usize = 5;
+    pub const MAX_MAP_SIZE: usize = 100;
+    pub const MAX_TEAMS: usize = 4;
+
+    pub fn from_settings(settings: &RoomSettings) -> Option<RoomConfig> {
+        let map_size = settings.map_size as usize;
+        let playable_tiles = map_size.saturating_sub(2) * map_size.saturating_sub(2);
+
+        let valid = Self::is_valid_players(settings.min_players, settings.max_players)
+            && settings.winner_points > 0
+            && (Self::MIN_MAP_SIZE..=Self::MAX_MAP_SIZE).contains(&map_size)
+            && playable_tiles >= settings.max_players as usize
+            && Self::is_valid_teams(settings.teams as usize, settings.min_players as usize)
+            && (settings.mode != GameModeKind::Timed || settings.game_duration.as_secs() > 0)
+            && !matches!(settings.sudden_death, Some(time) if time.as_secs() == 0)
+            && !matches!(settings.arena_time_limit, Some(time) if time.as_secs() == 0);
+
+        match valid {
+            true => Some(RoomConfig {
+                min_players: settings.min_players,
+                max_players: settings.max_players,
+                late_join: settings.late_join,
+                game: GameConfig {
+                    map_size,
+                    winner_points: settings.winner_points as usize,
+                    teams: settings.teams as usize,
+                    friendly_fire: settings.friendly_fire,
+                    mode: settings.mode,
+                    duration: settings.game_duration,
+                    sudden_death: settings.sudden_death,
+                    arena_time_limit: settings.arena_time_limit,
+                    tiebreak: settings.tiebreak,
+                },
+            }),
+            false => None,
+        }
+    }
+
+    pub fn is_valid_players(min_players: u8, max_players: u8) -> bool {
+        min_players > 0
+            && min_players <= max_players
+            && max_players as usize <= util::character_symbols().count()
+    }
+
+    /// Free for all (0 teams) or between 2 and `MAX_TEAMS`,
+    /// with at least a player per team in the smallest game.
+    pub fn is_valid_teams(teams: usize, min_players: usize) -> bool {
+        teams == 0 || (2..=Self::MAX_TEAMS).contains(&teams) && teams <= min_players
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= Self::MAX_NAME_LEN
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+pub struct Room {
+    pub name: String,
+    pub config: RoomConfig,
+    pub session: RoomSession<String>,
+    pub game: Option<Game>,
+    pub match_id: Option<String>, // Identifies the game in the logs
+    pub waiting_arena_from: Option<Instant>,
+    pub countdown: Option<Instant>, // When the next game starts
+    pub subscriptions: HashSet<Endpoint>,
+    pub chosen_teams: HashMap<String, usize>,
+    pub ready_players: HashSet<String>, // Logged players waiting for the next game
+    pub persistent: bool,
+}
+
+impl Room {
+    pub fn new(name: &str, config: RoomConfig, persistent: bool) -> Room {
+        Room {
+            name: name.into(),
+            session: RoomSession::new(config.max_players as usize),
+            config,
+            game: None,
+            match_id: None,
+            waiting_arena_from: None,
+            countdown: None,
+            subscriptions: HashSet::new(),
+            chosen_teams: HashMap::new(),
+            ready_players: HashSet::new(),
+            persistent,
+        }
+    }
+
+    pub fn logged_players(&self, ratings: &RatingDatabase) -> Vec<LoggedPlayer> {
+        self.session
+            .sessions()
+            .map(|session| LoggedPlayer {
+                name: session.user().clone(),
+                symbol: session.symbol(),
+                team: self.chosen_teams.get(session.user()).map(|team| *team as u8),
+                rating: ratings.rating(session.user()),
+                ready: self.ready_players.contains(session.user()),
+                playing: self.is_playing(session.symbol()),
+            })
+            .collect()
+    }
+
+    /// Checks if the character is a player of the current game.
+    pub fn is_playing(&self, symbol: char) -> bool {
+        matches!(&self.game, Some(game) if game.players().contains_key(&symbol))
+    }
+
+    /// Endpoints of the connected players of the current game.
+    pub fn game_endpoints(&self) -> Vec<Endpoint> {
+        self.session
+            .sessions()
+            .filter(|session| self.is_playing(session.symbol()))
+            .filter_map(|session| *session.safe_endpoint())
+            .collect()
+    }
+
+    /// Connected players in the lobby ready for the next game.
+    pub fn ready_sessions(&self) -> impl Iterator<Item = &Session<String>> {
+        self.session.sessions().filter(move |session| {
+            session.safe_endpoint().is_some()
+                && !self.is_playing(session.symbol())
+                && self.ready_players.contains(session.user())
+        })
+    }
+
+    /// There are enough ready players to start a game, placed at least in two teams.
+    pub fn can_start_game(&self) -> bool {
+        let ready_players = self.ready_sessions().count();
+        let enough_teams = match self.has_teams() {
+            true => self.balanced_teams().values().collect::<HashSet<_>>().len() >= 2,
+            false => true,
+        };
+        ready_players >= self.config.min_players as usize && enough_teams
+    }
+
+    pub fn has_teams(&self) -> bool {
+        self.config.game.teams > 0
+    }
+
+    /// Maximum number of players of a team to keep the teams balanced.
+    pub fn team_capacity(&self, players: usize) -> usize {
+        players.div_ceil(self.config.game.teams.max(1))
+    }
+
+    pub fn can_choose_team(&self, team: usize) -> bool {
+        let members = self.chosen_teams.values().filter(|&&chosen| chosen == team).count();
+        team < self.config.game.teams
+            && members < self.team_capacity(self.config.max_players as usize)
+    }
+
+    /// Team of each ready player, counting the players of the current game if any.
+    /// Players without a chosen team, or whose chosen team is full,
+    /// are placed in the smallest teams.
+    pub fn balanced_teams(&self) -> HashMap<String, usize> {
+        let mut teams = HashMap::new();
+        if !self.has_teams() {
+            return teams
+        }
+
+        let mut team_sizes = vec![0; self.config.game.teams];
+        if let Some(game) = &self.game {
+            for team in game.players().values().filter_map(|player| player.team()) {
+                team_sizes[team] += 1;
+            }
+        }
+
+        let mut ready_players =
+            self.ready_sessions().map(|session| session.user().clone()).collect::<Vec<_>>();
+        ready_players.sort();
+
+        let players = team_sizes.iter().sum::<usize>() + ready_players.len();
+        let capacity = self.team_capacity(players);
+
+        let mut unassigned = Vec::new();
+        for name in ready_players {
+            match self.chosen_teams.get(&name) {
+                Some(&team) if team_sizes[team] < capacity => {
+                    team_sizes[team] += 1;
+                    teams.insert(name, team);
+                }
+                _ => unassigned.push(name),
+            }
+        }
+
+        for name in unassigned {
+            let (team, _) = team_sizes.iter().enumerate().min_by_key(|(_, &size)| size).unwrap();
+            team_sizes[team] += 1;
+            teams.insert(name, team);
+        }
+        teams
+    }
+
+    pub fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            settings: RoomSettings {
+                name: self.name.clone(),
+                min_players: self.config.min_players,
+                max_players: self.config.max_players,
+                map_size: self.config.game.map_size as u16,
+                winner_points: self.config.game.winner_points as u16,
+                teams: self.config.game.teams as u8,
+                friendly_fire: self.config.game.friendly_fire,
+                mode: self.config.game.mode,
+                game_duration: self.config.game.duration,
+                sudden_death: self.config.game.sudden_death,
+                arena_time_limit: self.config.game.arena_time_limit,
+                tiebreak: self.config.game.tiebreak,
+                late_join: self.config.late_join,
+            },
+            logged_players: self.session.sessions().count() as u8,
+            in_game: self.game.is_some(),
+        }
+    }
+}
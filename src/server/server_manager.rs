@@ -1,16 +1,27 @@
-use super::session::{RoomSession, SessionStatus};
+use super::session::{SessionStatus};
+use super::room::{Room, RoomConfig};
+use super::chat::{ChatRateLimiter};
+use super::input::{InputLimiter, Violation};
+use super::admin::{self, AdminCommand, Target};
+use super::bans::{BanList};
+use super::metrics::{Metrics, Gauges};
+use super::ratings::{RatingDatabase, MatchResult};
 use super::game::{Game};
-use super::game::arena::{Arena};
 
 use crate::message::{
     ClientMessage, ServerMessage, ServerInfo, GameInfo, ArenaInfo, LoginStatus, LoggedKind,
-    EntityData, Frame, GameEvent, SpellData,
+    EntityData, Frame, GameEvent, KillCause, SpellData, ItemData, RoomSettings, RoomStatus,
+    PlayerInfo, PlayerData,
 };
 use crate::encoding::{self, Encoder};
+use crate::datagram::{Datagram};
+use crate::challenge::{self, Nonce};
 use crate::version::{self, Compatibility};
 use crate::direction::{Direction};
+use crate::vec2::{Vec2};
 use crate::ids::{SessionToken, SkillId};
 use crate::util::{self};
+use crate::logger::{self};
 
 use message_io::node::{self, NodeHandler, NodeListener, NodeEvent};
 use message_io::network::{Endpoint, Transport, NetEvent};
@@ -18,27 +29,53 @@ use message_io::network::{Endpoint, Transport, NetEvent};
 use itertools::{Itertools};
 
 use std::time::{Duration, Instant};
-use std::collections::{HashSet};
+use std::collections::{HashMap, HashSet};
+use std::path::{PathBuf};
+use std::net::{IpAddr};
 
 lazy_static! {
     static ref GAME_STEP_DURATION: Duration = Duration::from_secs_f32(1.0 / 30.0);
 }
 
+/// Room where the clients are placed if they do not join any other room.
+pub const MAIN_ROOM: &str = "main";
+
 #[derive(Debug)]
 enum Signal {
-    AsyncCreateGame, // Could take time in processing
-    AsyncStartArena, // Generated Eventually
-    GameStep,        // Generated Eventually
-    Close,           // Main loop control
+    AsyncCreateGame(String), // Generated Eventually, at the end of the countdown
+    AsyncStartArena(String), // Generated Eventually
+    GameStep(String),        // Generated Eventually
+    Admin(AdminCommand),     // Written in the console
+    CheckBanList,            // Generated Eventually
+    Close,                   // Ctrl-C, starts the shutdown
+    Stop,                    // Main loop control
+}
+
+/// Progress of the server shutdown.
+enum Shutdown {
+    WaitingArenas(String), //reason
+    Grace,                 // The clients have been notified
 }
 
 pub struct Config {
     pub tcp_port: u16,
     pub udp_port: u16,
-    pub players_number: u8,
-    pub map_size: usize,
-    pub winner_points: usize,
+    pub main_room: RoomConfig,
+    pub max_rooms: usize,
     pub arena_waiting: Duration,
+    pub lobby_countdown: Duration,
+    pub chat_max_messages: usize,
+    pub chat_period: Duration,
+    pub input_move_tolerance: f32,
+    pub input_max_casts: usize,
+    pub input_max_strikes: usize,
+    pub ratings_file: Option<PathBuf>,
+    pub ban_file: Option<PathBuf>,
+    pub max_connections_per_ip: usize,
+    pub max_subscriptions: usize,
+    pub metrics_port: Option<u16>,
+    pub shutdown_grace: Duration,
+    pub shutdown_wait_arena: bool,
 }
 
 pub struct ServerManager {
@@ -46,30 +83,45 @@ pub struct ServerManager {
     encoder: Encoder,
     node: NodeHandler<Signal>,
     listener: Option<NodeListener<Signal>>,
-    subscriptions: HashSet<Endpoint>,
-    room: RoomSession<char>,
-    game: Option<Game>,
-    waiting_arena_from: Option<Instant>,
+    rooms: HashMap<String, Room>,
+    joined_rooms: HashMap<Endpoint, String>,
+    chat_limiter: ChatRateLimiter,
+    input_limiter: InputLimiter,
+    ratings: RatingDatabase,
+    bans: BanList,
+    connections: HashSet<Endpoint>, // Tcp endpoints
+    reconnect_challenges: HashMap<Endpoint, (String, Nonce)>, // Player name and nonce sent
+    metrics: Metrics,
+    shutdown: Option<Shutdown>,
 }
 
 impl ServerManager {
+    /// Maximum number of players sent in the leaderboard.
+    const LEADERBOARD_SIZE: usize = 20;
+
+    const SHUTDOWN_REASON: &'static str = "closed by the administrator";
+
+    /// Time between checks for changes in the ban list file.
+    const BAN_LIST_CHECK_PERIOD: Duration = Duration::from_secs(2);
+
     pub fn new(config: Config) -> Option<ServerManager> {
+        let ratings = RatingDatabase::load(config.ratings_file.clone())?;
+        let bans = BanList::load(config.ban_file.clone())?;
         let (node, listener) = node::split();
-        /*
-        Network::split_and_map_from_adapter(|adapter_event| match adapter_event {
-            AdapterEvent::Added(endpoint) => Event::Connected(endpoint),
-            AdapterEvent::Data(endpoint, data) => match encoding::decode(&data) {
-                Some(message) => Event::Message(endpoint, message),
-                None => Event::DeserializationError(endpoint),
-            },
-            AdapterEvent::Removed(endpoint) => Event::Disconnected(endpoint),
-        });
-        */
 
         let node_closer = node.clone();
         ctrlc::set_handler(move || node_closer.signals().send_with_priority(Signal::Close))
             .unwrap();
 
+        if config.ban_file.is_some() {
+            node.signals().send_with_timer(Signal::CheckBanList, Self::BAN_LIST_CHECK_PERIOD);
+        }
+
+        let node_admin = node.clone();
+        std::thread::spawn(move || {
+            admin::read_console(|command| node_admin.signals().send(Signal::Admin(command)))
+        });
+
         let network_interface = "0.0.0.0";
         if node
             .network()
@@ -85,94 +137,377 @@ impl ServerManager {
             return None
         }
 
+        if let Some(port) = config.metrics_port {
+            // The raw tcp transport is only used by the metrics http endpoint
+            if node.network().listen(Transport::Tcp, (network_interface, port)).is_err() {
+                log::error!("Can not run metrics endpoint on TCP port {}", port);
+                return None
+            }
+            log::info!("Metrics available on http://{}:{}/metrics", network_interface, port);
+        }
+
         log::info!(
-            "Server running on ports {} (tcp) and {} (udp) for {} players",
+            "Server running on ports {} (tcp) and {} (udp). Room '{}' for {}-{} players ({})",
             config.tcp_port,
             config.udp_port,
-            config.players_number
+            MAIN_ROOM,
+            config.main_room.min_players,
+            config.main_room.max_players,
+            config.main_room.game.mode.name(),
         );
+        if let Some(rule) = config.main_room.late_join {
+            log::info!("Late join into running games, with catch-up rule '{}'", rule.name());
+        }
+
+        let main_room = Room::new(MAIN_ROOM, config.main_room.clone(), true);
 
         Some(ServerManager {
             encoder: Encoder::new(),
             node,
             listener: Some(listener),
-            subscriptions: HashSet::new(),
-            room: RoomSession::new(config.players_number as usize),
-            game: None,
-            waiting_arena_from: None,
+            rooms: vec![(MAIN_ROOM.into(), main_room)].into_iter().collect(),
+            joined_rooms: HashMap::new(),
+            chat_limiter: ChatRateLimiter::new(config.chat_max_messages, config.chat_period),
+            input_limiter: InputLimiter::new(
+                config.input_move_tolerance,
+                config.input_max_casts,
+                config.input_max_strikes,
+            ),
+            ratings,
+            bans,
+            connections: HashSet::new(),
+            reconnect_challenges: HashMap::new(),
+            metrics: Metrics::new(),
+            shutdown: None,
             config,
         })
     }
 
     fn send_to_client(&mut self, endpoint: Endpoint, message: ServerMessage) {
-        self.node.network().send(endpoint, self.encoder.encode(message));
+        let output_data = self.encoder.encode(message);
+        self.node.network().send(endpoint, output_data);
+        self.metrics.data_sent(endpoint, output_data.len());
     }
 
     fn send_to_all_clients(&mut self, endpoints: Vec<Endpoint>, message: ServerMessage) {
         let output_data = self.encoder.encode(message);
         for endpoint in endpoints {
             self.node.network().send(endpoint, output_data);
+            self.metrics.data_sent(endpoint, output_data.len());
+        }
+    }
+
+    fn is_metrics_endpoint(endpoint: Endpoint) -> bool {
+        endpoint.resource_id().adapter_id() == Transport::Tcp.id()
+    }
+
+    /// Name of the room where the client is, or the main room if it did not join any.
+    fn joined_room(&self, endpoint: Endpoint) -> String {
+        self.joined_rooms.get(&endpoint).cloned().unwrap_or_else(|| MAIN_ROOM.into())
+    }
+
+    /// Name of the room where the session of the endpoint is.
+    /// Fast endpoints are not registered in any room, so the session is searched in all of them.
+    fn session_room(&self, endpoint: Endpoint) -> String {
+        if self.joined_rooms.contains_key(&endpoint) {
+            return self.joined_room(endpoint)
         }
+        self.rooms
+            .iter()
+            .find(|(_, room)| room.session.session_by_any_endpoint(endpoint).is_some())
+            .map(|(room_name, _)| room_name.clone())
+            .unwrap_or_else(|| MAIN_ROOM.into())
     }
 
     pub fn run(mut self) {
         let listener = self.listener.take().unwrap();
-        listener.for_each(move |event| match event {
+        listener.for_each(move |event| {
+            let context = self.log_context(&event);
+            logger::with_context(context, || self.process_event(event));
+            self.process_pending_shutdown();
+        });
+    }
+
+    /// Fields of the logs written while processing the event.
+    fn log_context(&self, event: &NodeEvent<Signal>) -> logger::Context {
+        let (endpoint, room_name) = match event {
+            NodeEvent::Network(NetEvent::Connected(endpoint, _))
+            | NodeEvent::Network(NetEvent::Disconnected(endpoint))
+            | NodeEvent::Network(NetEvent::Message(endpoint, _)) => (Some(*endpoint), None),
+            NodeEvent::Signal(Signal::AsyncCreateGame(room_name))
+            | NodeEvent::Signal(Signal::AsyncStartArena(room_name))
+            | NodeEvent::Signal(Signal::GameStep(room_name)) => (None, Some(room_name.clone())),
+            NodeEvent::Signal(_) => (None, None),
+        };
+
+        let room_name = room_name.or_else(|| {
+            let endpoint = endpoint?;
+            match self.joined_rooms.get(&endpoint) {
+                Some(room_name) => Some(room_name.clone()),
+                None => self
+                    .rooms
+                    .iter()
+                    .find(|(_, room)| room.session.session_by_any_endpoint(endpoint).is_some())
+                    .map(|(room_name, _)| room_name.clone()),
+            }
+        });
+
+        let room = room_name.as_ref().and_then(|room_name| self.rooms.get(room_name));
+        let game = room.and_then(|room| room.game.as_ref());
+        logger::Context {
+            endpoint: endpoint.map(|endpoint| endpoint.addr().to_string()),
+            symbol: room
+                .zip(endpoint)
+                .and_then(|(room, endpoint)| room.session.session_by_any_endpoint(endpoint))
+                .map(|session| session.symbol()),
+            arena: game.map(|game| game.arena_number()).filter(|&number| number > 0),
+            match_id: room.and_then(|room| room.match_id.clone()),
+            room: room_name,
+        }
+    }
+
+    fn process_event(&mut self, event: NodeEvent<Signal>) {
+        match event {
             NodeEvent::Signal(signal) => match signal {
-                Signal::AsyncCreateGame => self.process_create_game(),
-                Signal::AsyncStartArena => self.process_start_arena(),
-                Signal::GameStep => self.process_game_step(),
-                Signal::Close => {
+                Signal::AsyncCreateGame(room_name) => self.process_create_game(room_name),
+                Signal::AsyncStartArena(room_name) => self.process_start_arena(room_name),
+                Signal::GameStep(room_name) => self.process_game_step(room_name),
+                Signal::Admin(command) => self.process_admin(command),
+                Signal::CheckBanList => {
+                    self.process_check_ban_list();
+                    let signal = Signal::CheckBanList;
+                    self.node.signals().send_with_timer(signal, Self::BAN_LIST_CHECK_PERIOD);
+                }
+                Signal::Close => self.process_close(Self::SHUTDOWN_REASON.into()),
+                Signal::Stop => {
                     log::info!("Closing server");
                     self.node.stop();
                 }
             },
             NodeEvent::Network(network) => match network {
-                NetEvent::Connected(endpoint, _) => log::trace!("{} has connected", endpoint),
+                NetEvent::Connected(endpoint, _) if Self::is_metrics_endpoint(endpoint) => (),
+                NetEvent::Connected(endpoint, _) => self.process_connection(endpoint),
+                NetEvent::Disconnected(endpoint) if Self::is_metrics_endpoint(endpoint) => (),
                 NetEvent::Disconnected(endpoint) => {
                     log::trace!("{} has disconnected", endpoint);
                     self.process_disconnection(endpoint);
                 }
+                NetEvent::Message(endpoint, data) if Self::is_metrics_endpoint(endpoint) => {
+                    self.process_metrics_request(endpoint, data);
+                }
                 NetEvent::Message(endpoint, data) => {
                     log::trace!("Message from {}", endpoint.addr());
+                    if endpoint.resource_id().adapter_id() == Transport::Udp.id() {
+                        // The udp endpoints are never removed: they share the listener socket.
+                        return self.process_datagram(endpoint, data)
+                    }
+
                     match encoding::decode::<ClientMessage>(data) {
-                        Some(message) => match message {
-                            ClientMessage::Version(client_version) => {
-                                self.process_version(endpoint, &client_version);
-                            }
-                            ClientMessage::SubscribeServerInfo => {
-                                self.process_subscribe_server_info(endpoint);
-                            }
-                            ClientMessage::Login(user) => {
-                                self.process_login(endpoint, user);
-                            }
-                            ClientMessage::Logout => {
-                                self.process_logout(endpoint);
-                            }
-                            ClientMessage::ConnectUdp(session_token) => {
-                                self.process_connect_udp(endpoint, session_token);
-                            }
-                            ClientMessage::TrustUdp => {
-                                self.process_trust_udp(endpoint);
-                            }
-                            ClientMessage::MovePlayer(direction) => {
-                                self.process_move_player(endpoint, direction);
-                            }
-                            ClientMessage::CastSkill(direction, id) => {
-                                self.process_cast_skill(endpoint, direction, id);
-                            }
-                        },
+                        Some(message) => self.process_client_message(endpoint, message),
                         None => {
                             log::error!(
                                 "{} sends an unknown message. Connection rejected",
                                 endpoint
                             );
+                            self.metrics.decode_failed();
+                            self.process_disconnection(endpoint);
                             self.node.network().remove(endpoint.resource_id());
                         }
                     };
                 }
             },
-        });
+        }
+    }
+
+    fn process_client_message(&mut self, endpoint: Endpoint, message: ClientMessage) {
+        match message {
+            ClientMessage::Version(client_version) => {
+                self.process_version(endpoint, &client_version);
+            }
+            ClientMessage::ListRooms => {
+                self.process_list_rooms(endpoint);
+            }
+            ClientMessage::CreateRoom(settings) => {
+                self.process_create_room(endpoint, settings);
+            }
+            ClientMessage::JoinRoom(room_name) => {
+                self.process_join_room(endpoint, room_name);
+            }
+            ClientMessage::SubscribeServerInfo => {
+                self.process_subscribe_server_info(endpoint);
+            }
+            ClientMessage::QueryLeaderboard => {
+                self.process_query_leaderboard(endpoint);
+            }
+            ClientMessage::Login(name, symbol) => {
+                self.process_login(endpoint, name, symbol);
+            }
+            ClientMessage::Reconnect(player_name) => {
+                self.process_reconnect(endpoint, player_name);
+            }
+            ClientMessage::ReconnectProof(proof) => {
+                self.process_reconnect_proof(endpoint, proof);
+            }
+            ClientMessage::Logout => {
+                self.process_logout(endpoint);
+            }
+            ClientMessage::ChooseTeam(team) => {
+                self.process_choose_team(endpoint, team);
+            }
+            ClientMessage::Ready(ready) => {
+                self.process_ready(endpoint, ready);
+            }
+            ClientMessage::Chat(text) => {
+                self.process_chat(endpoint, text);
+            }
+            ClientMessage::ConnectUdp => {
+                log::warn!("{} sends an udp handshake out of a datagram", endpoint.addr());
+            }
+            ClientMessage::TrustUdp => {
+                self.process_trust_udp(endpoint);
+            }
+            ClientMessage::MovePlayer(direction) => {
+                self.process_move_player(endpoint, direction);
+            }
+            ClientMessage::CastSkill(direction, id) => {
+                self.process_cast_skill(endpoint, direction, id);
+            }
+        }
+    }
+
+    /// Only the datagrams authenticated with the key of a session and not received before
+    /// are processed.
+    fn process_datagram(&mut self, udp_endpoint: Endpoint, data: &[u8]) {
+        let datagram = match Datagram::decode(data) {
+            Some(datagram) => datagram,
+            None => {
+                self.metrics.decode_failed();
+                return log::warn!("{} sends an unknown datagram", udp_endpoint.addr())
+            }
+        };
+
+        let session = self
+            .rooms
+            .values_mut()
+            .find_map(|room| room.session.session_by_udp_id_mut(datagram.session_id));
+
+        let accepted = match session {
+            Some(session) => session.open(&datagram),
+            None => false,
+        };
+
+        if !accepted {
+            let payload = encoding::decode::<ClientMessage>(&datagram.payload);
+            if let Some(ClientMessage::ConnectUdp) = payload {
+                self.metrics.udp_handshake_failed();
+            }
+            return log::warn!("{} sends a datagram not authenticated or repeated", udp_endpoint)
+        }
+
+        let session = self
+            .rooms
+            .values_mut()
+            .find_map(|room| room.session.session_by_udp_id_mut(datagram.session_id))
+            .unwrap();
+
+        match encoding::decode::<ClientMessage>(&datagram.payload) {
+            Some(ClientMessage::ConnectUdp) => {
+                log::trace!("Attached udp endpoint to session '{}'", session.token());
+                session.set_untrusted_fast_endpoint(udp_endpoint);
+                let data = session.seal(self.encoder.encode(ServerMessage::UdpConnected));
+                self.node.network().send(udp_endpoint, &data);
+                self.metrics.data_sent(udp_endpoint, data.len());
+            }
+            Some(message @ ClientMessage::MovePlayer(..))
+            | Some(message @ ClientMessage::CastSkill(..)) => {
+                if *session.trusted_fast_endpoint() != Some(udp_endpoint) {
+                    return log::warn!("{} sends input by an untrusted endpoint", udp_endpoint)
+                }
+                // An older move arrived later than a newer input: it is obsolete.
+                // Casts are independent actions, only the repeated ones are rejected by `open()`.
+                let is_move = matches!(message, ClientMessage::MovePlayer(..));
+                if is_move && !session.is_latest_datagram(datagram.sequence) {
+                    return log::trace!("Stale move from {} dropped", udp_endpoint)
+                }
+                self.process_client_message(udp_endpoint, message);
+            }
+            Some(message) => {
+                log::warn!("Unexpected udp message from {}: {:?}", udp_endpoint.addr(), message);
+            }
+            None => {
+                self.metrics.decode_failed();
+                log::warn!("{} sends an unknown udp message", udp_endpoint.addr());
+            }
+        }
+    }
+
+    /// Answers the http request of `/metrics`, closing the connection after it.
+    fn process_metrics_request(&mut self, endpoint: Endpoint, data: &[u8]) {
+        let request = String::from_utf8_lossy(data);
+        let path = request.lines().next().unwrap_or_default().split_whitespace().nth(1);
+        let (status, body) = match path {
+            Some("/metrics") => ("200 OK", self.metrics.render(&self.gauges())),
+            _ => ("404 Not Found", String::new()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+            Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        self.node.network().send(endpoint, response.as_bytes());
+        self.node.network().remove(endpoint.resource_id());
+    }
+
+    fn gauges(&self) -> Gauges {
+        let sessions = self.rooms.values().flat_map(|room| room.session.sessions());
+        Gauges {
+            connections: self.connections.len(),
+            sessions: sessions.filter(|session| session.safe_endpoint().is_some()).count(),
+            subscriptions: self.rooms.values().map(|room| room.subscriptions.len()).sum(),
+            rooms: self.rooms.len(),
+            games_running: self.rooms.values().filter(|room| room.game.is_some()).count(),
+        }
+    }
+
+    fn process_connection(&mut self, endpoint: Endpoint) {
+        let ip = endpoint.addr().ip();
+        let ip_connections = self.connections.iter().filter(|e| e.addr().ip() == ip).count();
+
+        if let Some(Shutdown::Grace) = self.shutdown {
+            log::trace!("Connection from {} rejected: closing server", endpoint.addr());
+            self.node.network().remove(endpoint.resource_id());
+        }
+        else if self.bans.is_banned(ip) {
+            log::warn!("Connection from {} rejected: banned ip", endpoint.addr());
+            self.node.network().remove(endpoint.resource_id());
+        }
+        else if ip_connections >= self.config.max_connections_per_ip {
+            log::warn!("Connection from {} rejected: too many connections", endpoint.addr());
+            self.node.network().remove(endpoint.resource_id());
+        }
+        else {
+            log::trace!("{} has connected", endpoint);
+            self.connections.insert(endpoint);
+        }
+    }
+
+    /// Closes the connections of the ips added to the ban list file.
+    fn process_check_ban_list(&mut self) {
+        if self.bans.reload_if_changed() {
+            let banned_ips = self
+                .connections
+                .iter()
+                .map(|endpoint| endpoint.addr().ip())
+                .filter(|ip| self.bans.is_banned(*ip))
+                .collect::<HashSet<_>>();
+
+            for ip in banned_ips {
+                self.kick_ip(ip, "banned");
+            }
+        }
     }
 
     fn process_version(&mut self, endpoint: Endpoint, client_version: &str) {
@@ -195,109 +530,312 @@ impl ServerManager {
         self.send_to_client(endpoint, message);
 
         if let Compatibility::None = compatibility {
+            self.process_disconnection(endpoint);
             self.node.network().remove(endpoint.resource_id());
         }
     }
 
+    fn process_list_rooms(&mut self, endpoint: Endpoint) {
+        let rooms = self
+            .rooms
+            .values()
+            .map(|room| room.summary())
+            .sorted_by(|a, b| a.settings.name.cmp(&b.settings.name))
+            .collect();
+
+        let message = ServerMessage::RoomList(rooms);
+        self.send_to_client(endpoint, message);
+    }
+
+    fn process_create_room(&mut self, endpoint: Endpoint, settings: RoomSettings) {
+        let status = if self.is_logged(endpoint) {
+            RoomStatus::AlreadyLogged
+        }
+        else if !RoomConfig::is_valid_name(&settings.name) {
+            RoomStatus::InvalidSettings
+        }
+        else if self.rooms.contains_key(&settings.name) {
+            RoomStatus::AlreadyExists
+        }
+        else if self.rooms.len() >= self.config.max_rooms {
+            RoomStatus::RoomLimit
+        }
+        else {
+            match RoomConfig::from_settings(&settings) {
+                Some(config) => {
+                    let room = Room::new(&settings.name, config, false);
+                    self.rooms.insert(settings.name.clone(), room);
+                    log::info!(
+                        "Room '{}' created for {}-{} players, current rooms: {}",
+                        settings.name,
+                        settings.min_players,
+                        settings.max_players,
+                        util::format::items_to_string(self.rooms.keys().sorted())
+                    );
+                    self.join_room(endpoint, &settings.name);
+                    RoomStatus::Created
+                }
+                None => RoomStatus::InvalidSettings,
+            }
+        };
+
+        if !matches!(status, RoomStatus::Created) {
+            log::warn!("Room '{}' can not be created: {:?}", settings.name, status);
+        }
+
+        let message = ServerMessage::RoomStatus(settings.name, status);
+        self.send_to_client(endpoint, message);
+    }
+
+    fn process_join_room(&mut self, endpoint: Endpoint, room_name: String) {
+        let status = if self.is_logged(endpoint) {
+            RoomStatus::AlreadyLogged
+        }
+        else if !self.rooms.contains_key(&room_name) {
+            RoomStatus::NotFound
+        }
+        else {
+            self.join_room(endpoint, &room_name);
+            RoomStatus::Joined
+        };
+
+        let message = ServerMessage::RoomStatus(room_name, status);
+        self.send_to_client(endpoint, message);
+    }
+
+    fn is_logged(&self, endpoint: Endpoint) -> bool {
+        let room = &self.rooms[&self.joined_room(endpoint)];
+        room.session.session_by_endpoint(endpoint).is_some()
+    }
+
+    fn join_room(&mut self, endpoint: Endpoint, room_name: &str) {
+        self.process_leave_room(endpoint);
+        self.joined_rooms.insert(endpoint, room_name.into());
+        log::trace!("Client {} has joined to room '{}'", endpoint.addr(), room_name);
+    }
+
+    fn process_leave_room(&mut self, endpoint: Endpoint) {
+        let room_name = self.joined_room(endpoint);
+        self.joined_rooms.remove(&endpoint);
+
+        if let Some(room) = self.rooms.get_mut(&room_name) {
+            if room.subscriptions.remove(&endpoint) {
+                log::trace!("Client {} has unsubscribed to server info", endpoint.addr());
+            }
+        }
+
+        self.remove_room_if_unused(&room_name);
+    }
+
+    fn remove_room_if_unused(&mut self, room_name: &str) {
+        if let Some(room) = self.rooms.get(room_name) {
+            let unused = !room.persistent
+                && room.game.is_none()
+                && room.session.sessions().count() == 0
+                && !self.joined_rooms.values().any(|name| name == room_name);
+
+            if unused {
+                self.rooms.remove(room_name);
+                log::info!(
+                    "Room '{}' removed, current rooms: {}",
+                    room_name,
+                    util::format::items_to_string(self.rooms.keys().sorted())
+                );
+            }
+        }
+    }
+
     fn process_subscribe_server_info(&mut self, endpoint: Endpoint) {
+        // A client that subscribes again keeps its place
+        let subscriptions = self
+            .rooms
+            .values()
+            .flat_map(|room| room.subscriptions.iter())
+            .filter(|&&subscribed| subscribed != endpoint)
+            .count();
+        if subscriptions >= self.config.max_subscriptions {
+            log::warn!("Subscription of {} rejected: too many subscriptions", endpoint.addr());
+            return self.close_connection(endpoint, "the server has too many clients")
+        }
+
+        let room_name = self.joined_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
         let info = ServerInfo {
+            room: room.name.clone(),
             udp_port: self.config.udp_port,
-            players_number: self.config.players_number,
-            map_size: self.config.map_size as u16,
-            winner_points: self.config.winner_points as u16,
-            logged_players: self.room.sessions().map(|session| *session.user()).collect(),
+            min_players: room.config.min_players,
+            max_players: room.config.max_players,
+            map_size: room.config.game.map_size as u16,
+            winner_points: room.config.game.winner_points as u16,
+            teams: room.config.game.teams as u8,
+            friendly_fire: room.config.game.friendly_fire,
+            mode: room.config.game.mode,
+            game_duration: room.config.game.duration,
+            sudden_death: room.config.game.sudden_death,
+            late_join: room.config.late_join,
+            countdown: room.countdown.map(|start| start.saturating_duration_since(Instant::now())),
+            logged_players: room.logged_players(&self.ratings),
         };
 
-        log::trace!("Client {} has subscribed to server info", endpoint.addr());
-        self.subscriptions.insert(endpoint);
+        log::trace!("Client {} has subscribed to server info of '{}'", endpoint.addr(), room_name);
+        room.subscriptions.insert(endpoint);
         let message = ServerMessage::StaticServerInfo(info);
         self.send_to_client(endpoint, message);
     }
 
-    fn process_login(&mut self, endpoint: Endpoint, player_symbol: char) {
-        let status = if !util::is_valid_character(player_symbol) {
-            log::warn!("Invalid character symbol '{}' has tried to login", player_symbol);
+    fn process_query_leaderboard(&mut self, endpoint: Endpoint) {
+        let leaderboard = self.ratings.leaderboard(Self::LEADERBOARD_SIZE);
+        self.send_to_client(endpoint, ServerMessage::Leaderboard(leaderboard));
+    }
+
+    fn process_login(&mut self, endpoint: Endpoint, player_name: String, symbol: Option<char>) {
+        let room_name = self.joined_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+
+        let status = if !util::is_valid_player_name(&player_name) {
+            log::warn!("Invalid player name '{}' has tried to login", player_name);
             LoginStatus::InvalidPlayerName
         }
         else {
-            match self.room.create_session(player_symbol, endpoint) {
-                SessionStatus::Created(token) => {
-                    let player_symbols =
-                        self.room.sessions().map(|session| session.user()).sorted();
+            match room.session.create_session(player_name.clone(), symbol, endpoint) {
+                SessionStatus::Created(token, symbol) => {
+                    let player_names =
+                        room.session.sessions().map(|session| session.user()).sorted();
 
                     log::info!(
-                        "New player logged: {}, current players: {}",
-                        player_symbol,
-                        util::format::items_to_string(player_symbols)
+                        "[{}] New player logged: {} ({}), current players: {}",
+                        room_name,
+                        player_name,
+                        symbol,
+                        util::format::items_to_string(player_names)
                     );
-                    LoginStatus::Logged(token, LoggedKind::FirstTime)
-                }
-                SessionStatus::Recycled(token) => {
-                    log::info!("Player '{}' reconnected", player_symbol);
-                    LoginStatus::Logged(token, LoggedKind::Reconnection)
+                    LoginStatus::Logged(Some(token), symbol, LoggedKind::FirstTime)
                 }
                 SessionStatus::AlreadyLogged => {
                     log::warn!(
-                        "Player '{}' has tried to login but the character symbol is already logged",
-                        player_symbol
+                        "Player '{}' has tried to login but the player name is already logged",
+                        player_name
                     );
                     LoginStatus::AlreadyLogged
                 }
                 SessionStatus::Full => {
                     log::warn!(
                         "Player '{}' has tried to login but the player limit has been reached",
-                        player_symbol
+                        player_name
                     );
                     LoginStatus::PlayerLimit
                 }
+                SessionStatus::Recycled(..) | SessionStatus::InvalidToken => unreachable!(),
             }
         };
 
         log::trace!(
             "{} with player '{}' attempts to login. Status: {:?}",
             endpoint.addr(),
-            player_symbol,
+            player_name,
             status
         );
 
-        let message = ServerMessage::LoginStatus(player_symbol, status);
+        let message = ServerMessage::LoginStatus(player_name, status);
+        self.send_to_client(endpoint, message);
+
+        if let LoginStatus::Logged(_, _, kind) = status {
+            self.process_logged(endpoint, room_name, kind);
+        }
+    }
+
+    /// The session token is not sent again: the client must prove that it knows it.
+    fn process_reconnect(&mut self, endpoint: Endpoint, player_name: String) {
+        if self.is_logged(endpoint) {
+            return log::warn!("Client {} has tried to reconnect being logged", endpoint.addr())
+        }
+
+        let nonce: Nonce = rand::random();
+        self.reconnect_challenges.insert(endpoint, (player_name, nonce));
+        self.send_to_client(endpoint, ServerMessage::ReconnectChallenge(nonce));
+    }
+
+    fn process_reconnect_proof(&mut self, endpoint: Endpoint, proof: Vec<u8>) {
+        let room_name = self.joined_room(endpoint);
+        if self.is_logged(endpoint) {
+            return log::warn!("Client {} has tried to reconnect being logged", endpoint.addr())
+        }
+
+        let (player_name, nonce) = match self.reconnect_challenges.remove(&endpoint) {
+            Some(challenge) => challenge,
+            None => {
+                return log::warn!("Client {} has sent a proof without challenge", endpoint.addr())
+            }
+        };
+
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        let token = room
+            .session
+            .sessions()
+            .find(|session| *session.user() == player_name)
+            .map(|session| session.token())
+            .filter(|&token| challenge::verify(token, &nonce, &proof));
+
+        // The session is only taken from its current endpoint with a valid proof
+        let status = match token.map(|token| room.session.reclaim_session(token, endpoint)) {
+            Some(SessionStatus::Recycled(symbol)) => {
+                log::info!("[{}] Player '{}' reconnected", room_name, player_name);
+                LoginStatus::Logged(None, symbol, LoggedKind::Reconnection)
+            }
+            _ => {
+                log::warn!(
+                    "Client {} has tried to reconnect to '{}' as '{}' with an invalid proof",
+                    endpoint.addr(),
+                    room_name,
+                    player_name
+                );
+                LoginStatus::InvalidToken
+            }
+        };
+
+        let message = ServerMessage::LoginStatus(player_name, status);
         self.send_to_client(endpoint, message);
 
-        if let LoginStatus::Logged(_, kind) = status {
-            match kind {
-                LoggedKind::FirstTime => {
-                    let player_symbols =
-                        self.room.sessions().map(|session| *session.user()).collect();
+        if let LoginStatus::Logged(_, _, kind) = status {
+            self.process_logged(endpoint, room_name, kind);
+        }
+    }
 
-                    let message = ServerMessage::DynamicServerInfo(player_symbols);
-                    let subscriptions = self.subscriptions.iter().cloned().collect();
-                    self.send_to_all_clients(subscriptions, message);
+    fn process_logged(&mut self, endpoint: Endpoint, room_name: String, kind: LoggedKind) {
+        let room = &self.rooms[&room_name];
+        let session = room.session.session_by_endpoint(endpoint).unwrap();
+        let message = ServerMessage::UdpKey(session.udp_id(), *session.udp_key());
+        self.send_to_client(endpoint, message);
 
-                    if self.game.is_none() && self.room.is_full() {
-                        self.node.signals().send(Signal::AsyncCreateGame);
+        let room = &self.rooms[&room_name];
+        match kind {
+            LoggedKind::FirstTime => {
+                let logged_players = room.logged_players(&self.ratings);
+                let message = ServerMessage::DynamicServerInfo(logged_players);
+                let subscriptions = room.subscriptions.iter().cloned().collect();
+                self.send_to_all_clients(subscriptions, message);
+            }
+            LoggedKind::Reconnection => {
+                let symbol = room.session.session_by_endpoint(endpoint).unwrap().symbol();
+                if let (true, Some(game)) = (room.is_playing(symbol), &room.game) {
+                    let mut messages = vec![Self::create_start_game_message(game)];
+
+                    if let Some(waiting_from) = room.waiting_arena_from {
+                        let duration = Instant::now().duration_since(waiting_from);
+                        let waiting = self
+                            .config
+                            .arena_waiting
+                            .checked_sub(duration)
+                            .unwrap_or_else(|| Duration::new(0, 0));
+                        messages.push(ServerMessage::WaitArena(waiting));
                     }
-                }
-                LoggedKind::Reconnection => {
-                    if let Some(game) = &self.game {
-                        let message = Self::create_start_game_message(game);
-                        self.send_to_client(endpoint, message);
 
-                        if let Some(waiting_from) = self.waiting_arena_from {
-                            let duration = Instant::now().duration_since(waiting_from);
-                            let waiting = self
-                                .config
-                                .arena_waiting
-                                .checked_sub(duration)
-                                .unwrap_or_else(|| Duration::new(0, 0));
-                            let message = ServerMessage::WaitArena(waiting);
-                            self.send_to_client(endpoint, message);
-                        }
+                    if game.arena().is_some() {
+                        messages.push(Self::create_start_arena_message(game));
+                    }
 
-                        let game = self.game.as_ref().unwrap();
-                        if game.arena().is_some() {
-                            let message = Self::create_start_arena_message(game);
-                            self.send_to_client(endpoint, message);
-                        }
+                    for message in messages {
+                        self.send_to_client(endpoint, message);
                     }
                 }
             }
@@ -305,239 +843,983 @@ impl ServerManager {
     }
 
     fn process_logout(&mut self, endpoint: Endpoint) {
-        if self.game.is_some() {
-            if let Some(session) = self.room.session_by_endpoint_mut(endpoint) {
-                session.disconnect();
-                log::info!("Player '{}' disconnected", session.user());
-            }
+        let room_name = self.joined_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+
+        let playing = match room.session.session_by_endpoint(endpoint) {
+            Some(session) => room.is_playing(session.symbol()),
+            None => return,
+        };
+
+        if playing {
+            // Kept until the end of the game to be reclaimed
+            let session = room.session.session_by_endpoint_mut(endpoint).unwrap();
+            session.disconnect();
+            log::info!("[{}] Player '{}' disconnected", room_name, session.user());
         }
-        else if let Some(session) = self.room.remove_session_by_endpoint(endpoint) {
-            let player_symbols =
-                self.room.sessions().map(|session| *session.user()).collect::<Vec<_>>();
+        else if let Some(session) = room.session.remove_session_by_endpoint(endpoint) {
+            self.input_limiter.remove(session.token());
+            room.chosen_teams.remove(session.user());
+            room.ready_players.remove(session.user());
+            let logged_players = room.logged_players(&self.ratings);
 
             log::info!(
-                "Player '{}' logout, current players: {} ",
+                "[{}] Player '{}' logout, current players: {} ",
+                room_name,
                 session.user(),
-                util::format::items_to_string(player_symbols.iter().sorted())
+                util::format::items_to_string(logged_players.iter().map(|p| &p.name).sorted())
             );
 
-            let message = ServerMessage::DynamicServerInfo(player_symbols);
-            let subscriptions = self.subscriptions.iter().cloned().collect();
+            let message = ServerMessage::DynamicServerInfo(logged_players);
+            let subscriptions = room.subscriptions.iter().cloned().collect();
             self.send_to_all_clients(subscriptions, message);
+            self.process_check_countdown(&room_name);
         }
     }
 
-    fn process_connect_udp(&mut self, udp_endpoint: Endpoint, session_token: SessionToken) {
-        match self.room.session_mut(session_token) {
-            Some(session) => {
-                log::trace!("Attached udp endpoint to session '{}'", session_token);
-                session.set_untrusted_fast_endpoint(udp_endpoint);
-                let message = ServerMessage::UdpConnected;
-                self.send_to_client(udp_endpoint, message);
+    fn process_choose_team(&mut self, endpoint: Endpoint, team: Option<u8>) {
+        let room_name = self.joined_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+
+        let (player_name, symbol) = match room.session.session_by_endpoint(endpoint) {
+            Some(session) => (session.user().clone(), session.symbol()),
+            None => return log::warn!("Not logged client {} has tried to choose a team", endpoint),
+        };
+
+        if room.is_playing(symbol) {
+            return log::warn!("[{}] '{}' can not change the team in game", room_name, player_name)
+        }
+
+        match team.map(|team| team as usize) {
+            Some(team) if room.can_choose_team(team) => {
+                log::info!("[{}] Player '{}' has chosen the team {}", room_name, player_name, team);
+                room.chosen_teams.insert(player_name, team);
             }
-            None => log::warn!(
-                "Attempt to attach udp endpoint to non-existent session '{}'",
-                session_token
-            ),
+            Some(team) => {
+                return log::warn!(
+                    "[{}] Player '{}' can not choose the team {}",
+                    room_name,
+                    player_name,
+                    team
+                )
+            }
+            None => {
+                room.chosen_teams.remove(&player_name);
+            }
+        }
+
+        let message = ServerMessage::DynamicServerInfo(room.logged_players(&self.ratings));
+        let subscriptions = room.subscriptions.iter().cloned().collect();
+        self.send_to_all_clients(subscriptions, message);
+    }
+
+    fn process_ready(&mut self, endpoint: Endpoint, ready: bool) {
+        let room_name = self.joined_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+
+        let (player_name, symbol) = match room.session.session_by_endpoint(endpoint) {
+            Some(session) => (session.user().clone(), session.symbol()),
+            None => return log::warn!("Not logged client {} has tried to be ready", endpoint),
+        };
+
+        if room.is_playing(symbol) {
+            return log::warn!("[{}] '{}' can not be ready in game", room_name, player_name)
+        }
+
+        let changed = match ready {
+            true => room.ready_players.insert(player_name.clone()),
+            false => room.ready_players.remove(&player_name),
+        };
+
+        if changed {
+            log::info!(
+                "[{}] Player '{}' is {}, ready players: {}",
+                room_name,
+                player_name,
+                if ready { "ready" } else { "not ready" },
+                util::format::items_to_string(room.ready_players.iter().sorted())
+            );
+
+            let message = ServerMessage::DynamicServerInfo(room.logged_players(&self.ratings));
+            let subscriptions = room.subscriptions.iter().cloned().collect();
+            self.send_to_all_clients(subscriptions, message);
+            self.process_check_countdown(&room_name);
+        }
+    }
+
+    /// Starts the countdown of the next game when there are enough ready players,
+    /// or cancels it if they are no longer enough.
+    fn process_check_countdown(&mut self, room_name: &str) {
+        let room = match self.rooms.get_mut(room_name) {
+            Some(room) => room,
+            None => return,
+        };
+
+        let enough_players =
+            self.shutdown.is_none() && room.game.is_none() && room.can_start_game();
+
+        let message = match (enough_players, room.countdown) {
+            (true, None) => {
+                let countdown = self.config.lobby_countdown;
+                log::info!("[{}] Game starting in {} seconds", room_name, countdown.as_secs());
+                room.countdown = Some(Instant::now() + countdown);
+                let signal = Signal::AsyncCreateGame(room_name.into());
+                self.node.signals().send_with_timer(signal, countdown);
+                ServerMessage::Countdown(Some(countdown))
+            }
+            (false, Some(_)) => {
+                log::info!("[{}] Countdown cancelled: not enough ready players", room_name);
+                room.countdown = None;
+                ServerMessage::Countdown(None)
+            }
+            _ => return,
+        };
+
+        let endpoints = room.subscriptions.iter().cloned().collect();
+        self.send_to_all_clients(endpoints, message);
+    }
+
+    fn process_chat(&mut self, endpoint: Endpoint, text: String) {
+        let room_name = self.joined_room(endpoint);
+        let room = &self.rooms[&room_name];
+
+        let player_name = match room.session.session_by_endpoint(endpoint) {
+            Some(session) => session.user().clone(),
+            None => return log::warn!("Not logged client {} has tried to chat", endpoint.addr()),
+        };
+
+        if !util::is_valid_chat_message(&text) {
+            return log::warn!("[{}] Invalid chat message from '{}'", room_name, player_name)
+        }
+
+        if !self.chat_limiter.allow(endpoint) {
+            return log::warn!("[{}] Chat rate limit exceeded by '{}'", room_name, player_name)
         }
+
+        log::info!("[{}] Chat '{}': {}", room_name, player_name, text);
+
+        let endpoints = room
+            .subscriptions
+            .iter()
+            .cloned()
+            .chain(room.session.safe_endpoints())
+            .collect::<HashSet<_>>();
+
+        let message = ServerMessage::Chat(player_name, text);
+        self.send_to_all_clients(endpoints.into_iter().collect(), message);
     }
 
     fn process_trust_udp(&mut self, related_tcp_endpoint: Endpoint) {
-        match self.room.session_by_endpoint_mut(related_tcp_endpoint) {
+        let room_name = self.joined_room(related_tcp_endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        match room.session.session_by_endpoint_mut(related_tcp_endpoint) {
             Some(session) => match session.trust_in_fast_endpoint() {
                 Some(_) => log::trace!("Trusted udp endpoint for session '{}'", session.token()),
-                None => log::error!(
-                    "Attempt to trust into a non-existent udp endpoint. Session '{}'",
-                    session.token()
-                ),
+                None => {
+                    log::error!(
+                        "Attempt to trust into a non-existent udp endpoint. Session '{}'",
+                        session.token()
+                    );
+                    self.metrics.udp_handshake_failed();
+                }
             },
             None => log::error!("Attempt to trust an udp endpoint in an non-existent session"),
         }
     }
 
-    fn process_create_game(&mut self) {
-        log::info!("Starting new game");
-        let player_symbols = self.room.sessions().map(|session| *session.user());
-        let game = Game::new(self.config.map_size, self.config.winner_points, player_symbols);
+    fn process_create_game(&mut self, room_name: String) {
+        if self.shutdown.is_some() {
+            return log::trace!("[{}] Game not created: closing server", room_name)
+        }
+
+        let room = match self.rooms.get_mut(&room_name) {
+            // Ignored if the countdown was cancelled or restarted
+            Some(room) if matches!(room.countdown, Some(start) if Instant::now() >= start) => room,
+            _ => return,
+        };
+
+        room.countdown = None;
+        let match_id = format!("{}-{}", room_name, chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        room.match_id = Some(match_id.clone());
+        logger::update_context(|context| context.match_id = Some(match_id.clone()));
+        log::info!("[{}] Starting new game: match {}", room_name, match_id);
+
+        let teams = room.balanced_teams();
+        if room.has_teams() {
+            log::info!(
+                "[{}] Teams: {}",
+                room_name,
+                util::format::pair_items_to_string(teams.iter().sorted_by_key(|(_, &team)| team))
+            );
+        }
+
+        let players = room
+            .ready_sessions()
+            .map(|session| {
+                let team = teams.get(session.user()).copied();
+                (session.user().clone(), session.symbol(), team)
+            })
+            .collect::<Vec<_>>();
+
+        for (player_name, _, _) in &players {
+            room.ready_players.remove(player_name);
+        }
+
+        let game = Game::new(room.config.game.clone(), players.into_iter());
+        log::info!("[{}] Game mode: {}", room_name, game.mode().kind().name());
 
         let message = Self::create_start_game_message(&game);
-        self.send_to_all_clients(self.room.safe_endpoints(), message);
+        room.game = Some(game);
+        let endpoints = room.game_endpoints();
+        let info_message = ServerMessage::DynamicServerInfo(room.logged_players(&self.ratings));
+        let subscriptions = room.subscriptions.iter().cloned().collect();
 
-        self.game = Some(game);
-        self.process_wait_arena();
+        self.send_to_all_clients(endpoints, message);
+        self.send_to_all_clients(subscriptions, info_message);
 
-        self.node.signals().send(Signal::GameStep);
+        self.process_wait_arena(&room_name);
+
+        self.node.signals().send(Signal::GameStep(room_name));
     }
 
-    fn process_wait_arena(&mut self) {
+    fn process_wait_arena(&mut self, room_name: &str) {
         log::trace!(
-            "Initializing next arena in {} seconds...",
+            "[{}] Initializing next arena in {} seconds...",
+            room_name,
             self.config.arena_waiting.as_secs_f32()
         );
 
-        let message = ServerMessage::WaitArena(self.config.arena_waiting);
-        self.send_to_all_clients(self.room.safe_endpoints(), message);
+        let room = self.rooms.get_mut(room_name).unwrap();
+        room.waiting_arena_from = Some(Instant::now());
 
-        self.node.signals().send_with_timer(Signal::AsyncStartArena, self.config.arena_waiting);
+        let message = ServerMessage::WaitArena(self.config.arena_waiting);
+        let endpoints = room.game_endpoints();
+        self.send_to_all_clients(endpoints, message);
 
-        self.waiting_arena_from = Some(Instant::now());
+        let signal = Signal::AsyncStartArena(room_name.into());
+        self.node.signals().send_with_timer(signal, self.config.arena_waiting);
     }
 
-    fn process_start_arena(&mut self) {
-        self.waiting_arena_from = None;
-        self.game.as_mut().unwrap().create_new_arena();
-        let game = self.game.as_ref().unwrap();
+    fn process_start_arena(&mut self, room_name: String) {
+        if self.shutdown.is_some() {
+            return log::trace!("[{}] Arena not started: closing server", room_name)
+        }
+
+        let room = match self.rooms.get_mut(&room_name) {
+            Some(room) if room.game.is_some() => room,
+            _ => return, // The game finished while waiting for the arena.
+        };
+
+        room.waiting_arena_from = None;
+        self.process_late_join(&room_name);
+
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        room.game.as_mut().unwrap().create_new_arena();
+        let game = room.game.as_ref().unwrap();
         let arena = game.arena().unwrap();
-        log::info!("Start arena {}", game.arena_number());
+        let arena_number = game.arena_number();
+        logger::update_context(|context| context.arena = Some(arena_number));
+        log::info!("[{}] Start arena {}", room_name, game.arena_number());
 
         let entities = arena.entities();
         let player_positions = game
             .players()
-            .iter()
-            .map(|(symbol, player)| {
+            .values()
+            .map(|player| {
                 let entity = entities.get(&player.entity_id());
 
                 let position = match entity {
                     Some(entity) => entity.position().to_string(),
                     None => "-".into(),
                 };
-                (symbol, position)
+                (player.name(), position)
             })
             .collect::<Vec<_>>();
 
         log::trace!("Player positions: {}", util::format::pair_items_to_string(player_positions));
 
         let message = Self::create_start_arena_message(game);
-        self.send_to_all_clients(self.room.safe_endpoints(), message);
+        let endpoints = room.game_endpoints();
+        self.send_to_all_clients(endpoints, message);
     }
 
-    fn process_game_step(&mut self) {
-        log::trace!("Processing step");
+    /// Adds the ready players of the lobby to the running game, if the room allows it.
+    /// They are spawned in the next arena.
+    fn process_late_join(&mut self, room_name: &str) {
+        let room = self.rooms.get_mut(room_name).unwrap();
+        let rule = match room.config.late_join {
+            Some(rule) => rule,
+            None => return,
+        };
 
-        let game = self.game.as_mut().unwrap();
-        let previous_players = game.living_players().len();
+        let teams = room.balanced_teams();
+        let players = room
+            .ready_sessions()
+            .map(|session| {
+                let team = teams.get(session.user()).copied();
+                (session.user().clone(), session.symbol(), team)
+            })
+            .collect::<Vec<_>>();
 
-        game.step();
+        if players.is_empty() {
+            return
+        }
 
-        if let Some(arena) = game.arena() {
-            let message = Self::create_game_step_message(arena);
-            self.send_to_all_clients(self.room.faster_endpoints(), message);
+        let previous_endpoints = room.game_endpoints();
+        let game = room.game.as_mut().unwrap();
+        for (player_name, symbol, team) in players {
+            room.ready_players.remove(&player_name);
+            game.add_player(player_name.clone(), symbol, team, rule);
+            log::info!(
+                "[{}] Player '{}' joins the game with {} points{}",
+                room_name,
+                player_name,
+                game.players()[&symbol].points(),
+                team.map(|team| format!(" in team {}", team)).unwrap_or_default()
+            );
         }
 
-        let game = self.game.as_ref().unwrap();
-        let current_players = game.living_players().len();
+        let joined_endpoints = room
+            .game_endpoints()
+            .into_iter()
+            .filter(|endpoint| !previous_endpoints.contains(endpoint))
+            .collect();
+
+        let game = room.game.as_ref().unwrap();
+        let start_message = Self::create_start_game_message(game);
+        let joined_message = ServerMessage::PlayersJoined(Self::create_game_info(game));
+        let info_message = ServerMessage::DynamicServerInfo(room.logged_players(&self.ratings));
+        let subscriptions = room.subscriptions.iter().cloned().collect();
+
+        self.send_to_all_clients(previous_endpoints, joined_message);
+        self.send_to_all_clients(joined_endpoints, start_message);
+        self.send_to_all_clients(subscriptions, info_message);
+    }
+
+    fn process_game_step(&mut self, room_name: String) {
+        log::trace!("[{}] Processing step", room_name);
+        let step_start = Instant::now();
+
+        let room = match self.rooms.get_mut(&room_name) {
+            Some(room) if room.game.is_some() => room,
+            _ => return,
+        };
+
+        let game = room.game.as_mut().unwrap();
+        let deaths = game.step();
+
+        // Each player receives only the terrain changes that it can see
+        let players = room
+            .session
+            .sessions()
+            .filter(|session| room.is_playing(session.symbol()))
+            .filter_map(|session| {
+                let endpoint = (*session.safe_endpoint())?;
+                Some((endpoint, session.symbol()))
+            })
+            .collect::<Vec<_>>();
+
+        let game = room.game.as_mut().unwrap();
+        let terrain_changes = players
+            .into_iter()
+            .map(|(endpoint, symbol)| (endpoint, game.take_visible_terrain_changes(symbol)))
+            .filter(|(_, changes)| !changes.is_empty())
+            .collect::<Vec<_>>();
+
+        for (endpoint, changes) in terrain_changes {
+            self.send_to_client(endpoint, ServerMessage::TerrainChanged(changes));
+        }
+
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        let game = room.game.as_mut().unwrap();
+        if game.arena().is_some() {
+            // Each player receives only what it can see
+            let encoder = &mut self.encoder;
+            let packets = room
+                .session
+                .sessions_mut()
+                .filter(|session| game.players().contains_key(&session.symbol()))
+                .filter_map(|session| {
+                    let sight = game.sight(session.symbol());
+                    let message = Self::create_game_step_message(game, sight.as_deref());
+                    session.seal_for_faster_endpoint(encoder.encode(message))
+                })
+                .collect::<Vec<_>>();
+
+            for (endpoint, data) in packets {
+                self.node.network().send(endpoint, &data);
+                self.metrics.data_sent(endpoint, data.len());
+            }
+        }
+
+        let room = &self.rooms[&room_name];
+        let game = room.game.as_ref().unwrap();
+
+        if !deaths.is_empty() {
+            let mut events = Vec::new();
+            for death in &deaths {
+                let victim = &game.players()[&death.victim];
+                let cause = match death.killer {
+                    Some(killer) if killer == death.victim => KillCause::Oneself,
+                    Some(killer) => KillCause::Player(game.player_index(killer)),
+                    None => KillCause::Environment,
+                };
+
+                match death.killer {
+                    Some(killer) if killer != death.victim => {
+                        let killer = game.players()[&killer].name();
+                        log::info!("[{}] {} killed {}", room_name, killer, victim.name());
+                    }
+                    _ => log::info!("[{}] {} died", room_name, victim.name()),
+                }
+
+                let victim_index = game.player_index(death.victim);
+                events.push(GameEvent::PlayerKilled(victim_index, cause));
+            }
 
-        if current_players < previous_players {
             let player_total_points_pairs = game
                 .pole()
                 .iter()
-                .map(|player| (player.character().symbol(), player.points()))
+                .map(|player| (player.name(), player.points()))
                 .collect::<Vec<_>>();
 
-            log::info!("Points: {}", util::format::pair_items_to_string(player_total_points_pairs));
+            log::info!(
+                "[{}] Points: {}",
+                room_name,
+                util::format::pair_items_to_string(player_total_points_pairs)
+            );
 
             let points = game.players().values().map(|player| player.points()).collect();
+            events.push(GameEvent::PlayerPointsUpdated(points));
+
+            if game.has_teams() {
+                let team_points = game.team_points().to_vec();
+                log::info!(
+                    "[{}] Team points: {}",
+                    room_name,
+                    util::format::pair_items_to_string(team_points.iter().enumerate())
+                );
+                events.push(GameEvent::TeamPointsUpdated(team_points));
+            }
 
-            let message = ServerMessage::GameEvent(GameEvent::PlayerPointsUpdated(points));
-            self.send_to_all_clients(self.room.safe_endpoints(), message);
+            let endpoints = room.game_endpoints();
+            for event in events {
+                self.send_to_all_clients(endpoints.clone(), ServerMessage::GameEvent(event));
+            }
         }
 
-        let game = self.game.as_ref().unwrap();
+        self.metrics.game_step_processed(step_start.elapsed());
+
+        let room = &self.rooms[&room_name];
+        let game = room.game.as_ref().unwrap();
         if game.has_finished() {
-            log::info!("End game");
-            let message = ServerMessage::FinishGame;
-            self.send_to_all_clients(self.room.safe_endpoints(), message);
-            self.process_reset();
+            log::info!("[{}] End game", room_name);
+            let message = ServerMessage::FinishGame(game.match_stats());
+            let endpoints = room.game_endpoints();
+            let results = Self::match_results(game);
+            self.send_to_all_clients(endpoints, message);
+            self.ratings.record_match(&room_name, results);
+            self.metrics.game_played();
+            self.process_reset(&room_name);
         }
         else {
-            if current_players <= 1 && self.waiting_arena_from.is_none() {
-                log::info!("End arena");
-                self.process_wait_arena();
+            if game.has_arena_finished() && room.waiting_arena_from.is_none() {
+                log::info!("[{}] End arena", room_name);
+                self.process_wait_arena(&room_name);
             }
-            self.node.signals().send_with_timer(Signal::GameStep, *GAME_STEP_DURATION);
+            let signal = Signal::GameStep(room_name);
+            self.node.signals().send_with_timer(signal, *GAME_STEP_DURATION);
         }
     }
 
     fn process_move_player(&mut self, endpoint: Endpoint, direction: Direction) {
-        match self.room.session_by_endpoint(endpoint) {
-            Some(session) => match self.game.as_mut() {
-                Some(game) => {
-                    let player = game.player_mut(*session.user()).unwrap();
-                    if player.is_alive() {
-                        player.walk(direction);
+        let room_name = self.session_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        let violation = match room.session.session_by_any_endpoint(endpoint) {
+            Some(session) => match room.game.as_mut().and_then(|g| g.player_mut(session.symbol())) {
+                Some(player) => {
+                    let speed = player.character().speed_base();
+                    if !self.input_limiter.allow_move(session.token(), speed) {
+                        Some(Violation::MoveRate)
+                    }
+                    else if player.is_alive() && !player.walk(direction) {
+                        Some(Violation::QueueFull)
+                    }
+                    else {
+                        None
                     }
                 }
-                None => log::warn!("Client attempted to move a player without a created game"),
+                None => {
+                    log::warn!("Client attempted to move a player out of a game");
+                    None
+                }
             },
-            None => log::warn!("Unlogged client attempted to move a player. Maybe an attack?"),
+            None => {
+                log::warn!("Unlogged client attempted to move a player. Maybe an attack?");
+                None
+            }
         };
+
+        if let Some(violation) = violation {
+            self.process_input_violation(&room_name, endpoint, violation);
+        }
     }
 
     fn process_cast_skill(&mut self, endpoint: Endpoint, direction: Direction, id: SkillId) {
-        match self.room.session_by_endpoint(endpoint) {
-            Some(session) => match self.game.as_mut() {
-                Some(game) => {
-                    let player = game.player_mut(*session.user()).unwrap();
-                    if player.is_alive() {
-                        player.cast(direction, id);
+        let room_name = self.session_room(endpoint);
+        let room = self.rooms.get_mut(&room_name).unwrap();
+        let violation = match room.session.session_by_any_endpoint(endpoint) {
+            Some(session) => match room.game.as_mut().and_then(|g| g.player_mut(session.symbol())) {
+                Some(player) => {
+                    if !player.has_skill(id) {
+                        Some(Violation::UnknownSkill)
+                    }
+                    else if !self.input_limiter.allow_cast(session.token()) {
+                        Some(Violation::CastRate)
+                    }
+                    else if player.is_alive() && !player.cast(direction, id) {
+                        Some(Violation::QueueFull)
+                    }
+                    else {
+                        None
                     }
                 }
-                None => log::warn!("Client attempted to cast a skill without a created game"),
+                None => {
+                    log::warn!("Client attempted to cast a skill out of a game");
+                    None
+                }
             },
-            None => log::warn!("Unlogged client attempted to cast a skill. Maybe an attack?"),
+            None => {
+                log::warn!("Unlogged client attempted to cast a skill. Maybe an attack?");
+                None
+            }
         };
+
+        if let Some(violation) = violation {
+            self.process_input_violation(&room_name, endpoint, violation);
+        }
     }
 
-    fn process_reset(&mut self) {
-        log::info!("Reset server");
-        self.game = None;
-        self.room.clear();
+    /// The rejected input counts as a strike. Too many strikes kick the player.
+    fn process_input_violation(
+        &mut self,
+        room_name: &str,
+        endpoint: Endpoint,
+        violation: Violation,
+    ) {
+        let room = &self.rooms[room_name];
+        let session = room.session.session_by_any_endpoint(endpoint).unwrap();
+        let token = session.token();
+        let (strikes, should_kick) = self.input_limiter.strike(token);
+        log::warn!(
+            "[{}] Input of '{}' rejected: {}. Strike {}/{}",
+            room_name,
+            session.user(),
+            violation.description(),
+            strikes,
+            self.input_limiter.max_strikes()
+        );
+
+        if should_kick {
+            let reason = format!("too many invalid inputs ({})", violation.description());
+            self.kick(room_name, token, &reason);
+        }
+    }
+
+    /// Closes the connection of the session, that can not be reclaimed again.
+    fn kick(&mut self, room_name: &str, token: SessionToken, reason: &str) {
+        let room = self.rooms.get_mut(room_name).unwrap();
+        let session = match room.session.session_mut(token) {
+            Some(session) => session,
+            None => return,
+        };
 
-        let player_symbols = self.room.sessions().map(|session| *session.user()).collect();
+        log::info!("[{}] Player '{}' kicked: {}", room_name, session.user(), reason);
+        session.kick();
+        let endpoint = *session.safe_endpoint();
+        self.input_limiter.remove(token);
 
-        let message = ServerMessage::DynamicServerInfo(player_symbols);
-        let subscriptions = self.subscriptions.iter().cloned().collect();
+        if let Some(endpoint) = endpoint {
+            self.close_connection(endpoint, reason);
+        }
+    }
+
+    /// Notifies the reason to the client before closing its connection.
+    fn close_connection(&mut self, endpoint: Endpoint, reason: &str) {
+        self.send_to_client(endpoint, ServerMessage::Kicked(reason.into()));
+        self.process_disconnection(endpoint);
+        self.node.network().remove(endpoint.resource_id());
+    }
+
+    fn process_admin(&mut self, command: AdminCommand) {
+        log::trace!("Admin command: {:?}", command);
+        match command {
+            AdminCommand::Help => println!("{}", admin::USAGE),
+            AdminCommand::Sessions => self.process_admin_sessions(),
+            AdminCommand::Kick(target) => self.process_admin_kick(target, false),
+            AdminCommand::Ban(target) => self.process_admin_kick(target, true),
+            AdminCommand::NextArena(room_name) => self.process_admin_next_arena(&room_name),
+            AdminCommand::Reset(room_name) => self.process_admin_reset(&room_name),
+            AdminCommand::WinnerPoints(room_name, points) => {
+                self.process_admin_settings(&room_name, Some(points), None);
+            }
+            AdminCommand::MapSize(room_name, size) => {
+                self.process_admin_settings(&room_name, None, Some(size));
+            }
+            AdminCommand::Announce(text) => self.announce(text),
+            AdminCommand::Shutdown(reason) => {
+                self.process_close(reason.unwrap_or_else(|| Self::SHUTDOWN_REASON.into()))
+            }
+        }
+    }
+
+    fn announce(&mut self, text: String) {
+        log::info!("Announcement: {}", text);
+        let endpoints = self.connections.iter().cloned().collect();
+        self.send_to_all_clients(endpoints, ServerMessage::Announcement(text));
+    }
+
+    fn has_running_arenas(&self) -> bool {
+        self.rooms.values().any(|room| room.game.is_some() && room.waiting_arena_from.is_none())
+    }
+
+    /// Starts the shutdown, waiting for the current arenas if configured.
+    /// A second request while shutting down closes the server immediately.
+    fn process_close(&mut self, reason: String) {
+        if self.shutdown.is_some() {
+            log::warn!("Forced close");
+            return self.node.signals().send_with_priority(Signal::Stop)
+        }
+
+        if self.config.shutdown_wait_arena && self.has_running_arenas() {
+            log::info!("Shutdown when the current arenas finish: {}", reason);
+            self.shutdown = Some(Shutdown::WaitingArenas(reason));
+            self.announce("The server will shut down at the end of the current arena".into());
+        }
+        else {
+            self.process_shutdown(reason);
+        }
+    }
+
+    fn process_shutdown(&mut self, reason: String) {
+        let grace = self.config.shutdown_grace;
+        log::info!("Shutting down in {} seconds: {}", grace.as_secs_f32(), reason);
+        self.shutdown = Some(Shutdown::Grace);
+
+        let endpoints = self.connections.iter().cloned().collect();
+        self.send_to_all_clients(endpoints, ServerMessage::Shutdown(reason, grace));
+        self.node.signals().send_with_timer(Signal::Stop, grace);
+    }
+
+    /// Continues the shutdown waiting for the arenas, once all of them have finished.
+    fn process_pending_shutdown(&mut self) {
+        if let Some(Shutdown::WaitingArenas(reason)) = &self.shutdown {
+            if !self.has_running_arenas() {
+                let reason = reason.clone();
+                self.process_shutdown(reason);
+            }
+        }
+    }
+
+    fn process_admin_sessions(&self) {
+        for (room_name, room) in self.rooms.iter().sorted_by_key(|(room_name, _)| *room_name) {
+            let status = match (&room.game, room.countdown) {
+                (Some(game), _) => format!("playing arena {}", game.arena_number()),
+                (None, Some(_)) => "counting down".into(),
+                (None, None) => "waiting for players".into(),
+            };
+            println!(
+                "[{}] {}/{} players (min {}), {}",
+                room_name,
+                room.session.sessions().count(),
+                room.config.max_players,
+                room.config.min_players,
+                status
+            );
+
+            for session in room.session.sessions().sorted_by_key(|session| session.symbol()) {
+                let connection = match session.safe_endpoint() {
+                    Some(endpoint) => endpoint.addr().to_string(),
+                    None => "disconnected".into(),
+                };
+                let udp = if session.trusted_fast_endpoint().is_some() { " (udp)" } else { "" };
+                let kicked = if session.is_kicked() { " (kicked)" } else { "" };
+                let lobby = match room.is_playing(session.symbol()) {
+                    true => " (playing)",
+                    false if room.ready_players.contains(session.user()) => " (ready)",
+                    false => "",
+                };
+                println!(
+                    "    {} {:<16} {}{}{}{}",
+                    session.symbol(),
+                    session.user(),
+                    connection,
+                    udp,
+                    lobby,
+                    kicked
+                );
+            }
+        }
+    }
+
+    fn process_admin_kick(&mut self, target: Target, ban: bool) {
+        let reason = match ban {
+            true => "banned by the administrator",
+            false => "by the administrator",
+        };
+
+        match target {
+            Target::Player(room_name, symbol) => {
+                let session = self
+                    .rooms
+                    .get(&room_name)
+                    .and_then(|room| room.session.sessions().find(|s| s.symbol() == symbol));
+
+                let (token, endpoint) = match session {
+                    Some(session) => (session.token(), *session.safe_endpoint()),
+                    None => return println!("No player '{}' in room '{}'", symbol, room_name),
+                };
+
+                match endpoint {
+                    // All the clients of its ip are affected by the ban
+                    Some(endpoint) if ban => {
+                        self.process_admin_kick(Target::Ip(endpoint.addr().ip()), true)
+                    }
+                    None if ban => {
+                        println!("The player is disconnected, its ip is unknown. Only kicked");
+                        self.kick(&room_name, token, reason);
+                    }
+                    _ => self.kick(&room_name, token, reason),
+                }
+            }
+            Target::Ip(ip) => {
+                if ban && self.bans.ban(ip) {
+                    log::info!("Ip {} banned", ip);
+                }
+                self.kick_ip(ip, reason);
+            }
+        }
+    }
+
+    /// Kicks the sessions of the ip and closes the rest of its connections.
+    fn kick_ip(&mut self, ip: IpAddr, reason: &str) {
+        let sessions = self
+            .rooms
+            .iter()
+            .flat_map(|(room_name, room)| {
+                room.session
+                    .sessions()
+                    .filter(|session| {
+                        matches!(session.safe_endpoint(), Some(e) if e.addr().ip() == ip)
+                    })
+                    .map(move |session| (room_name.clone(), session.token()))
+            })
+            .collect::<Vec<_>>();
+
+        for (room_name, token) in sessions {
+            self.kick(&room_name, token, reason);
+        }
+
+        // Clients without session
+        let endpoints = self
+            .connections
+            .iter()
+            .filter(|endpoint| endpoint.addr().ip() == ip)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for endpoint in endpoints {
+            log::info!("Client {} kicked: {}", endpoint.addr(), reason);
+            self.close_connection(endpoint, reason);
+        }
+    }
+
+    fn process_admin_next_arena(&mut self, room_name: &str) {
+        match self.rooms.get(room_name) {
+            Some(room) if room.game.is_some() => {
+                if room.waiting_arena_from.is_none() {
+                    log::info!("[{}] Arena finished by the administrator", room_name);
+                    self.process_wait_arena(room_name);
+                }
+            }
+            Some(_) => println!("There is no game in room '{}'", room_name),
+            None => println!("Room '{}' not found", room_name),
+        }
+    }
+
+    fn process_admin_reset(&mut self, room_name: &str) {
+        match self.rooms.get(room_name) {
+            Some(room) if room.game.is_some() => {
+                log::info!("[{}] Game finished by the administrator", room_name);
+                let game = room.game.as_ref().unwrap();
+                // Before the first arena the clients have nothing to finish
+                if game.arena().is_some() {
+                    let message = ServerMessage::FinishGame(game.match_stats());
+                    let endpoints = room.game_endpoints();
+                    self.send_to_all_clients(endpoints, message);
+                }
+                self.process_reset(room_name);
+            }
+            Some(_) => println!("There is no game in room '{}'", room_name),
+            None => println!("Room '{}' not found", room_name),
+        }
+    }
+
+    /// The settings are applied from the next game.
+    fn process_admin_settings(
+        &mut self,
+        room_name: &str,
+        winner_points: Option<usize>,
+        map_size: Option<usize>,
+    ) {
+        let room = match self.rooms.get_mut(room_name) {
+            Some(room) => room,
+            None => return println!("Room '{}' not found", room_name),
+        };
+
+        let mut settings = room.summary().settings;
+        if let Some(points) = winner_points {
+            settings.winner_points = points.min(u16::MAX as usize) as u16;
+        }
+        if let Some(size) = map_size {
+            settings.map_size = size.min(u16::MAX as usize) as u16;
+        }
+
+        match RoomConfig::from_settings(&settings) {
+            Some(config) => {
+                room.config.game.winner_points = config.game.winner_points;
+                room.config.game.map_size = config.game.map_size;
+                log::info!(
+                    "[{}] Settings for the next games: {} winner points, map size {}",
+                    room_name,
+                    config.game.winner_points,
+                    config.game.map_size
+                );
+            }
+            None => println!("Invalid value for room '{}'", room_name),
+        }
+    }
+
+    /// Finishes the game, keeping the connected players logged in the lobby for the next one.
+    fn process_reset(&mut self, room_name: &str) {
+        log::info!("[{}] Reset room", room_name);
+        let room = self.rooms.get_mut(room_name).unwrap();
+        room.game = None;
+        if let Some(match_id) = room.match_id.take() {
+            logger::close_match_log(&match_id);
+            logger::update_context(|context| context.match_id = None);
+        }
+        room.waiting_arena_from = None;
+
+        let left_sessions = room
+            .session
+            .sessions()
+            .filter(|session| session.safe_endpoint().is_none())
+            .map(|session| session.token())
+            .collect::<Vec<_>>();
+
+        for token in left_sessions {
+            let session = room.session.remove_session(token).unwrap();
+            room.chosen_teams.remove(session.user());
+            room.ready_players.remove(session.user());
+            self.input_limiter.remove(token);
+        }
+
+        let message = ServerMessage::DynamicServerInfo(room.logged_players(&self.ratings));
+        let subscriptions = room.subscriptions.iter().cloned().collect();
         self.send_to_all_clients(subscriptions, message);
+
+        self.remove_room_if_unused(room_name);
+        self.process_check_countdown(room_name);
     }
 
     fn process_disconnection(&mut self, endpoint: Endpoint) {
-        if self.subscriptions.remove(&endpoint) {
-            log::trace!("Client {} has unsubscribed to server info", endpoint.addr());
+        self.connections.remove(&endpoint);
+        let room_name = self.joined_room(endpoint);
+        if let Some(room) = self.rooms.get_mut(&room_name) {
+            if room.subscriptions.remove(&endpoint) {
+                log::trace!("Client {} has unsubscribed to server info", endpoint.addr());
+            }
         }
+        self.chat_limiter.remove(endpoint);
+        self.reconnect_challenges.remove(&endpoint);
         self.process_logout(endpoint);
+        self.process_leave_room(endpoint);
+    }
+
+    fn match_results(game: &Game) -> Vec<MatchResult> {
+        game.players()
+            .values()
+            .map(|player| MatchResult {
+                name: player.name().into(),
+                team: player.team(),
+                score: match player.team() {
+                    Some(team) => game.team_points()[team],
+                    None => player.points(),
+                },
+            })
+            .collect()
     }
 
     fn create_start_game_message(game: &Game) -> ServerMessage {
-        let game_info = GameInfo {
-            characters: game
-                .characters()
-                .iter()
-                .map(|(_, character)| (**character).clone())
-                .collect(),
+        ServerMessage::StartGame(Self::create_game_info(game))
+    }
+
+    fn create_game_info(game: &Game) -> GameInfo {
+        let match_stats = game.match_stats();
+        GameInfo {
+            characters: game.characters().values().map(|character| (**character).clone()).collect(),
             players: game
                 .players()
-                .iter()
-                .map(|(_, player)| (player.character().id(), player.points()))
+                .values()
+                .enumerate()
+                .map(|(index, player)| PlayerInfo {
+                    name: player.name().into(),
+                    character_id: player.character().id(),
+                    team: player.team().map(|team| team as u8),
+                    points: player.points(),
+                    kills: match_stats.iter().map(|stats| stats[index].kills).sum(),
+                    deaths: match_stats.iter().map(|stats| stats[index].deaths).sum(),
+                })
                 .collect(),
-        };
-
-        ServerMessage::StartGame(game_info)
+            team_points: game.team_points().to_vec(),
+            remaining_time: game.remaining_time(),
+        }
     }
 
     fn create_start_arena_message(game: &Game) -> ServerMessage {
         let arena_info = ArenaInfo {
             number: game.arena_number(),
-            players: game.players().iter().map(|(_, player)| player.entity_id()).collect(),
+            players: game.players().values().map(|player| player.entity_id()).collect(),
             ground: game.arena().unwrap().map().ground().clone(),
+            sudden_death: game.remaining_sudden_death(),
+            time_limit: game.remaining_arena_time(),
         };
 
         ServerMessage::StartArena(arena_info)
     }
 
-    fn create_game_step_message(arena: &Arena) -> ServerMessage {
+    fn create_game_step_message(game: &Game, sight: Option<&[bool]>) -> ServerMessage {
+        let arena = game.arena().unwrap();
+        let map = arena.map();
+        let is_visible = |position: Vec2| match sight {
+            Some(sight) => sight[position.y as usize * map.size() + position.x as usize],
+            None => true,
+        };
+
+        let players = game
+            .players()
+            .values()
+            .map(|player| match arena.entities().get(&player.entity_id()) {
+                Some(entity) => PlayerData { health: entity.health(), energy: entity.energy() },
+                None => PlayerData { health: 0, energy: 0 },
+            })
+            .collect();
+
         let entities = arena
             .entities()
             .values()
+            .filter(|entity| is_visible(entity.position()))
             .map(|entity| EntityData {
                 id: entity.id(),
                 character_id: entity.character().id(),
@@ -550,6 +1832,7 @@ impl ServerManager {
         let spells = arena
             .spells()
             .values()
+            .filter(|spell| is_visible(spell.position()))
             .map(|spell| SpellData {
                 id: spell.id(),
                 spec_id: spell.spec_id(),
@@ -557,6 +1840,13 @@ impl ServerManager {
             })
             .collect();
 
-        ServerMessage::GameStep(Frame { entities, spells })
+        let items = arena
+            .items()
+            .values()
+            .filter(|item| is_visible(item.position()))
+            .map(|item| ItemData { id: item.id(), kind: item.kind(), position: item.position() })
+            .collect();
+
+        ServerMessage::GameStep(Frame { players, entities, spells, items })
     }
 }
@@ -1,4 +1,6 @@
-use crate::ids::{SessionToken};
+use crate::ids::{SessionToken, UdpSessionId};
+use crate::datagram::{Datagram, SessionKey, Sender, ReplayWindow};
+use crate::util::{self};
 
 use rand::prelude::*;
 
@@ -7,10 +9,11 @@ use message_io::network::{Endpoint};
 use std::collections::HashMap;
 
 pub enum SessionStatus {
-    Created(SessionToken),
-    Recycled(SessionToken),
+    Created(SessionToken, char),
+    Recycled(char),
     AlreadyLogged,
     Full,
+    InvalidToken,
 }
 
 pub struct RoomSession<U> {
@@ -23,8 +26,8 @@ impl<U: Eq> RoomSession<U> {
         RoomSession { sessions: HashMap::new(), size }
     }
 
-    pub fn clear(&mut self) {
-        self.sessions.clear();
+    pub fn remove_session(&mut self, token: SessionToken) -> Option<Session<U>> {
+        self.sessions.remove(&token)
     }
 
     pub fn is_full(&self) -> bool {
@@ -35,6 +38,10 @@ impl<U: Eq> RoomSession<U> {
         self.sessions.values()
     }
 
+    pub fn sessions_mut(&mut self) -> impl Iterator<Item = &mut Session<U>> {
+        self.sessions.values_mut()
+    }
+
     pub fn session_mut(&mut self, token: SessionToken) -> Option<&mut Session<U>> {
         self.sessions.get_mut(&token)
     }
@@ -49,6 +56,10 @@ impl<U: Eq> RoomSession<U> {
         }
     }
 
+    pub fn session_by_udp_id_mut(&mut self, udp_id: UdpSessionId) -> Option<&mut Session<U>> {
+        self.sessions.values_mut().find(|session| session.udp_id() == udp_id)
+    }
+
     pub fn session_by_endpoint(&self, safe_endpoint: Endpoint) -> Option<&Session<U>> {
         self.sessions.values().find(|session| matches!(session.safe_endpoint(), Some(endpoint) if *endpoint == safe_endpoint))
     }
@@ -57,41 +68,72 @@ impl<U: Eq> RoomSession<U> {
         self.sessions.values_mut().find(|session| matches!(session.safe_endpoint(), Some(endpoint) if *endpoint == safe_endpoint))
     }
 
-    pub fn create_session(&mut self, user: U, safe_endpoint: Endpoint) -> SessionStatus {
-        let existing_session = self.sessions.values_mut().find(|session| *session.user() == user);
+    /// Finds the session by its safe endpoint or by its trusted fast endpoint.
+    pub fn session_by_any_endpoint(&self, endpoint: Endpoint) -> Option<&Session<U>> {
+        self.sessions.values().find(|session| {
+            *session.safe_endpoint() == Some(endpoint)
+                || *session.trusted_fast_endpoint() == Some(endpoint)
+        })
+    }
 
-        if let Some(session) = existing_session {
-            match session.safe_endpoint() {
-                Some(_) => SessionStatus::AlreadyLogged,
-                None => {
-                    session.set_safe_endpoint(safe_endpoint);
-                    SessionStatus::Recycled(session.token())
-                }
-            }
+    /// Creates a session for the user with the preferred symbol if it is available.
+    /// Otherwise, the first free symbol is assigned.
+    /// A session of the same user, even if disconnected, can only be recycled by its token.
+    pub fn create_session(
+        &mut self,
+        user: U,
+        preferred_symbol: Option<char>,
+        safe_endpoint: Endpoint,
+    ) -> SessionStatus {
+        if self.sessions().any(|session| *session.user() == user) {
+            SessionStatus::AlreadyLogged
         }
         else if self.is_full() {
             SessionStatus::Full
         }
         else {
-            let new_token = self.generate_unique_token();
-            let new_session = Session::new(new_token, user, safe_endpoint);
-            self.sessions.insert(new_token, new_session);
-            SessionStatus::Created(new_token)
+            match self.available_symbol(preferred_symbol) {
+                Some(symbol) => {
+                    let new_token = self.generate_unique_token();
+                    let new_session = Session::new(new_token, user, symbol, safe_endpoint);
+                    self.sessions.insert(new_token, new_session);
+                    SessionStatus::Created(new_token, symbol)
+                }
+                None => SessionStatus::Full,
+            }
         }
     }
 
-    pub fn safe_endpoints(&self) -> Vec<Endpoint> {
-        self.sessions().filter_map(|session| *session.safe_endpoint()).collect()
+    /// Recycles the session of the token with a new endpoint, keeping its symbol.
+    /// If the session is still connected, the previous endpoint is replaced,
+    /// so the knowledge of the token must be verified before.
+    /// A kicked session can not be recycled.
+    pub fn reclaim_session(
+        &mut self,
+        token: SessionToken,
+        safe_endpoint: Endpoint,
+    ) -> SessionStatus {
+        match self.sessions.get_mut(&token) {
+            Some(session) if !session.is_kicked() => {
+                session.disconnect();
+                session.set_safe_endpoint(safe_endpoint);
+                session.renew_udp_key();
+                SessionStatus::Recycled(session.symbol())
+            }
+            _ => SessionStatus::InvalidToken,
+        }
     }
 
-    /// Tries to return the fast endpoint, if this is not possible, the safe endpoint is returned.
-    pub fn faster_endpoints(&self) -> Vec<Endpoint> {
-        self.sessions()
-            .filter_map(|session| match session.trusted_fast_endpoint() {
-                Some(fast_endpoint) => Some(*fast_endpoint),
-                None => *session.safe_endpoint(),
-            })
-            .collect()
+    fn available_symbol(&self, preferred_symbol: Option<char>) -> Option<char> {
+        let is_free = |symbol: &char| self.sessions().all(|session| session.symbol() != *symbol);
+        preferred_symbol
+            .filter(|symbol| util::is_valid_character(*symbol))
+            .filter(is_free)
+            .or_else(|| util::character_symbols().find(is_free))
+    }
+
+    pub fn safe_endpoints(&self) -> Vec<Endpoint> {
+        self.sessions().filter_map(|session| *session.safe_endpoint()).collect()
     }
 
     fn generate_unique_token(&self) -> SessionToken {
@@ -108,19 +150,32 @@ impl<U: Eq> RoomSession<U> {
 pub struct Session<U> {
     token: SessionToken,
     user: U,
+    symbol: char,
     safe_endpoint: Option<Endpoint>,
     fast_endpoint: Option<Endpoint>,
     is_fast_endpoint_trusted: bool,
+    kicked: bool,
+    udp_id: UdpSessionId,
+    udp_key: SessionKey,
+    udp_sequence: u64, // Last sequence number sent by udp
+    udp_window: ReplayWindow,
 }
 
 impl<U> Session<U> {
-    fn new(token: SessionToken, user: U, safe_endpoint: Endpoint) -> Session<U> {
+    fn new(token: SessionToken, user: U, symbol: char, safe_endpoint: Endpoint) -> Session<U> {
+        let mut rng = rand::thread_rng();
         Session {
             token,
             user,
+            symbol,
             safe_endpoint: Some(safe_endpoint),
             fast_endpoint: None,
             is_fast_endpoint_trusted: false,
+            kicked: false,
+            udp_id: rng.gen(),
+            udp_key: rng.gen(),
+            udp_sequence: 0,
+            udp_window: ReplayWindow::default(),
         }
     }
 
@@ -128,6 +183,10 @@ impl<U> Session<U> {
         &self.user
     }
 
+    pub fn symbol(&self) -> char {
+        self.symbol
+    }
+
     pub fn token(&self) -> SessionToken {
         self.token
     }
@@ -147,9 +206,13 @@ impl<U> Session<U> {
         self.safe_endpoint = Some(endpoint);
     }
 
+    /// Sets a new fast endpoint that requires to be trusted.
+    /// A repeated handshake from the current endpoint keeps its trust.
     pub fn set_untrusted_fast_endpoint(&mut self, endpoint: Endpoint) {
-        self.fast_endpoint = Some(endpoint);
-        self.is_fast_endpoint_trusted = false;
+        if self.fast_endpoint != Some(endpoint) {
+            self.fast_endpoint = Some(endpoint);
+            self.is_fast_endpoint_trusted = false;
+        }
     }
 
     pub fn trust_in_fast_endpoint(&mut self) -> &Option<Endpoint> {
@@ -159,6 +222,59 @@ impl<U> Session<U> {
         &self.fast_endpoint
     }
 
+    pub fn udp_id(&self) -> UdpSessionId {
+        self.udp_id
+    }
+
+    pub fn udp_key(&self) -> &SessionKey {
+        &self.udp_key
+    }
+
+    /// Generates a new udp identity, invalidating the datagrams of the previous one.
+    fn renew_udp_key(&mut self) {
+        let mut rng = rand::thread_rng();
+        self.udp_id = rng.gen();
+        self.udp_key = rng.gen();
+        self.udp_sequence = 0;
+        self.udp_window = ReplayWindow::default();
+    }
+
+    /// Authenticates the payload to be sent by udp. Returns the datagram encoded.
+    pub fn seal(&mut self, payload: &[u8]) -> Vec<u8> {
+        self.udp_sequence += 1;
+        Datagram::seal(&self.udp_key, Sender::Server, self.udp_id, self.udp_sequence, payload)
+    }
+
+    /// Tries to return the fast endpoint, if this is not possible, the safe endpoint is returned.
+    /// The data to send by it is sealed if it is the udp one.
+    pub fn seal_for_faster_endpoint(&mut self, data: &[u8]) -> Option<(Endpoint, Vec<u8>)> {
+        match *self.trusted_fast_endpoint() {
+            Some(fast_endpoint) => Some((fast_endpoint, self.seal(data))),
+            None => self.safe_endpoint.map(|endpoint| (endpoint, data.to_vec())),
+        }
+    }
+
+    /// Checks that a datagram was sent by the client of this session and was not received yet.
+    pub fn open(&mut self, datagram: &Datagram) -> bool {
+        datagram.session_id == self.udp_id
+            && datagram.verify(&self.udp_key, Sender::Client)
+            && self.udp_window.accept(datagram.sequence)
+    }
+
+    /// Checks if the sequence number is the greatest one received by udp.
+    /// Datagrams with lower sequence numbers arrived unordered.
+    pub fn is_latest_datagram(&self, sequence: u64) -> bool {
+        self.udp_window.highest() == sequence
+    }
+
+    pub fn is_kicked(&self) -> bool {
+        self.kicked
+    }
+
+    pub fn kick(&mut self) {
+        self.kicked = true;
+    }
+
     pub fn disconnect(&mut self) {
         self.safe_endpoint = None;
         self.fast_endpoint = None;
@@ -0,0 +1,114 @@
+use crate::message::{Terrain};
+use crate::vec2::{Vec2};
+
+/// Maximum distance that a character can see.
+pub const SIGHT_RADIUS: i32 = 8;
+
+/// Transformations from the first octant to the others: (xx, xy, yx, yy).
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Computes the positions of the ground visible from any of the origins.
+/// The result is a mask indexed as the ground.
+/// Walls are visible but block the sight behind them (recursive shadowcasting).
+pub fn visible_area(ground: &[Terrain], size: usize, origins: &[Vec2]) -> Vec<bool> {
+    let mut shadowcaster = Shadowcaster { ground, size, visible: vec![false; ground.len()] };
+    for &origin in origins {
+        shadowcaster.mark_visible(origin);
+        for &transform in OCTANTS.iter() {
+            shadowcaster.cast_light(origin, 1, 1.0, 0.0, transform);
+        }
+    }
+    shadowcaster.visible
+}
+
+struct Shadowcaster<'a> {
+    ground: &'a [Terrain],
+    size: usize,
+    visible: Vec<bool>,
+}
+
+impl Shadowcaster<'_> {
+    fn index(&self, position: Vec2) -> Option<usize> {
+        let size = self.size as i32;
+        match position.x >= 0 && position.y >= 0 && position.x < size && position.y < size {
+            true => Some(position.y as usize * self.size + position.x as usize),
+            false => None,
+        }
+    }
+
+    fn mark_visible(&mut self, position: Vec2) {
+        if let Some(index) = self.index(position) {
+            self.visible[index] = true;
+        }
+    }
+
+    fn blocks_sight(&self, position: Vec2) -> bool {
+        match self.index(position) {
+            Some(index) => self.ground[index].is_wall(),
+            None => true,
+        }
+    }
+
+    fn cast_light(
+        &mut self,
+        origin: Vec2,
+        row: i32,
+        mut start: f32,
+        end: f32,
+        (xx, xy, yx, yy): (i32, i32, i32, i32),
+    ) {
+        if start < end {
+            return
+        }
+
+        let mut new_start = 0.0;
+        for distance in row..=SIGHT_RADIUS {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue
+                }
+                if end > left_slope {
+                    break
+                }
+
+                let position = origin + Vec2::xy(dx * xx + dy * xy, dx * yx + dy * yy);
+                if dx * dx + dy * dy <= SIGHT_RADIUS * SIGHT_RADIUS {
+                    self.mark_visible(position);
+                }
+
+                let wall = self.blocks_sight(position);
+                if blocked {
+                    if wall {
+                        new_start = right_slope;
+                    }
+                    else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                }
+                else if wall && distance < SIGHT_RADIUS {
+                    blocked = true;
+                    self.cast_light(origin, distance + 1, start, left_slope, (xx, xy, yx, yy));
+                    new_start = right_slope;
+                }
+            }
+
+            if blocked {
+                break
+            }
+        }
+    }
+}
@@ -3,7 +3,9 @@ use crate::ids::{SpellSpecId};
 use std::collections::{HashMap};
 
 pub struct SpellSpec {
+    #[allow(dead_code)]
     pub name: &'static str,
+    #[allow(dead_code)]
     pub description: &'static str,
     pub damage: i32,
     pub speed: f32,
@@ -1,9 +1,30 @@
+pub const MAX_PLAYER_NAME_LEN: usize = 12;
+
+pub fn is_valid_player_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_PLAYER_NAME_LEN
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+pub const MAX_CHAT_MESSAGE_LEN: usize = 100;
+
+pub fn is_valid_chat_message(text: &str) -> bool {
+    !text.trim().is_empty()
+        && text.chars().count() <= MAX_CHAT_MESSAGE_LEN
+        && !text.chars().any(|c| c.is_control())
+}
+
 pub fn is_valid_character_name(name: &str) -> bool {
-    name.len() == 1 && name.chars().all(|c| c.is_ascii_uppercase())
+    name.chars().count() == 1 && name.chars().all(is_valid_character)
 }
 
 pub fn is_valid_character(character: char) -> bool {
-    character.is_ascii_uppercase()
+    character.is_ascii_alphanumeric()
+}
+
+/// Symbols that can be assigned to players, by order of preference.
+pub fn character_symbols() -> impl Iterator<Item = char> {
+    ('A'..='Z').chain('a'..='z').chain('0'..='9')
 }
 
 pub mod format {
@@ -42,10 +42,9 @@ pub fn check(client_tag: &str, server_tag: &str) -> Compatibility {
     let server_version: Vec<_> = server_tag.split('.').collect();
     let client_version: Vec<_> = client_tag.split('.').collect();
 
-    if client_version.number(Major) != server_version.number(Major) {
-        Compatibility::None
-    }
-    else if client_version.number(Minor) != server_version.number(Minor) {
+    if client_version.number(Major) != server_version.number(Major)
+        || client_version.number(Minor) != server_version.number(Minor)
+    {
         Compatibility::None
     }
     else if client_version.number(Patch) != server_version.number(Patch) {